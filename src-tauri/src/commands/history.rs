@@ -1,38 +1,148 @@
-use crate::models::HistoryEntry;
-use tauri::AppHandle;
+use crate::models::{
+    HistoryEntry, HistoryExportFormat, HistoryFilter, HistoryImportReport, PruneReport,
+    RetentionSettings, RewriteAction,
+};
+use crate::services::history_store::HistoryStore;
+use crate::services::text_diff::{self, DiffGranularity, DiffSpan};
+use crate::services::{history_export, history_import};
+use chrono::Utc;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_store::StoreExt;
 
-// History operations are handled via the SQL plugin from the frontend.
-// These commands serve as stubs for the command handler registration
-// and could be expanded with Rust-side logic if needed.
+pub(crate) const SETTINGS_STORE: &str = "settings.json";
+const RETENTION_KEY: &str = "historyRetention";
+const RETENTION_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
 
 #[tauri::command]
-pub async fn init_history_db(_app: AppHandle) -> Result<(), String> {
-    // The SQL plugin handles DB creation via migrations registered in lib.rs
+pub async fn init_history_db(_state: State<'_, HistoryStore>) -> Result<(), String> {
+    // The store is opened and migrated during app setup in lib.rs
     Ok(())
 }
 
 #[tauri::command]
-pub async fn add_history_entry(_app: AppHandle, _entry: HistoryEntry) -> Result<(), String> {
-    // History inserts are done from the frontend via the SQL plugin
-    Ok(())
+pub async fn add_history_entry(
+    state: State<'_, HistoryStore>,
+    entry: HistoryEntry,
+) -> Result<(), String> {
+    state.insert(&entry)
 }
 
 #[tauri::command]
 pub async fn search_history(
-    _app: AppHandle,
-    _query: String,
-    _limit: i32,
+    state: State<'_, HistoryStore>,
+    filter: HistoryFilter,
+    limit: u32,
 ) -> Result<Vec<HistoryEntry>, String> {
-    // History queries are handled from the frontend via the SQL plugin
-    Ok(vec![])
+    state.search(&filter, limit)
 }
 
 #[tauri::command]
-pub async fn delete_history_entry(_app: AppHandle, _id: String) -> Result<(), String> {
-    Ok(())
+pub async fn delete_history_entry(state: State<'_, HistoryStore>, id: String) -> Result<(), String> {
+    state.delete(&id)
 }
 
 #[tauri::command]
-pub async fn clear_history(_app: AppHandle) -> Result<(), String> {
-    Ok(())
+pub async fn clear_history(state: State<'_, HistoryStore>) -> Result<(), String> {
+    state.clear()
+}
+
+/// Add a history entry's original and result to `action`'s examples and
+/// return the action for the frontend to save
+#[tauri::command]
+pub async fn promote_history_example(
+    state: State<'_, HistoryStore>,
+    mut action: RewriteAction,
+    entry_id: String,
+) -> Result<RewriteAction, String> {
+    let entry = state
+        .get(&entry_id)?
+        .ok_or_else(|| format!("No history entry '{}'", entry_id))?;
+    let example = (entry.original_text, entry.result_text);
+    if !action.examples.contains(&example) {
+        action.examples.push(example);
+    }
+    Ok(action)
+}
+
+/// Word- or character-level diff between an original and a rewritten text
+#[tauri::command]
+pub fn diff_texts(original: String, result: String, granularity: DiffGranularity) -> Vec<DiffSpan> {
+    text_diff::diff(&original, &result, granularity)
+}
+
+/// Export every entry matching `filter` to `path`, returning the number written
+#[tauri::command]
+pub async fn export_history(
+    state: State<'_, HistoryStore>,
+    filter: HistoryFilter,
+    format: HistoryExportFormat,
+    path: String,
+) -> Result<usize, String> {
+    let entries = state.search(&filter, u32::MAX)?;
+
+    let file = File::create(&path).map_err(|e| format!("Failed to create '{}': {}", path, e))?;
+    let mut writer = BufWriter::new(file);
+    history_export::write_entries(&mut writer, &entries, format)
+        .and_then(|_| writer.flush())
+        .map_err(|e| format!("Failed to write export: {}", e))?;
+
+    Ok(entries.len())
+}
+
+/// Merge a JSONL or CSV export into the local history. Entries whose id
+/// already exists are skipped; rows that fail validation are counted as invalid.
+#[tauri::command]
+pub async fn import_history(
+    state: State<'_, HistoryStore>,
+    path: String,
+) -> Result<HistoryImportReport, String> {
+    let format = history_import::format_for_path(Path::new(&path))?;
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+
+    let parsed = history_import::parse(&content, format)?;
+    let (inserted, skipped) = state.insert_many(&parsed.entries)?;
+
+    Ok(HistoryImportReport {
+        inserted,
+        skipped,
+        invalid: parsed.invalid,
+    })
+}
+
+/// Apply retention limits now, or report what would be removed when `dry_run` is set
+#[tauri::command]
+pub async fn prune_history(
+    state: State<'_, HistoryStore>,
+    settings: RetentionSettings,
+    dry_run: bool,
+) -> Result<PruneReport, String> {
+    state.prune(&settings, Utc::now(), dry_run)
+}
+
+/// Retention limits saved by the frontend in the settings store
+fn saved_retention_settings(app: &AppHandle) -> RetentionSettings {
+    app.store(SETTINGS_STORE)
+        .ok()
+        .and_then(|store| store.get(RETENTION_KEY))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+/// Prune history at startup and then periodically with the saved retention limits
+pub fn spawn_retention_task(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let settings = saved_retention_settings(&app);
+            let store = app.state::<HistoryStore>();
+            if let Err(e) = store.prune(&settings, Utc::now(), false) {
+                eprintln!("History pruning failed: {}", e);
+            }
+            tokio::time::sleep(RETENTION_INTERVAL).await;
+        }
+    });
 }
@@ -1,6 +1,20 @@
 use crate::models::*;
 use crate::services::response_normalizer;
 use std::time::Instant;
+use tokio::task::JoinSet;
+
+const TEMPERATURE: f32 = 0.3;
+/// Alternatives should differ from each other, so they are sampled hotter
+const CANDIDATE_TEMPERATURE: f32 = 0.9;
+
+/// Alternative completions of one prompt
+#[derive(Debug, Clone)]
+pub struct Candidates {
+    pub texts: Vec<String>,
+    /// Summed over every request made for them
+    pub tokens_used: Option<u64>,
+    pub duration_ms: u64,
+}
 
 #[tauri::command]
 pub async fn call_llm(
@@ -8,11 +22,138 @@ pub async fn call_llm(
     api_key: String,
     model: String,
     system_prompt: String,
+    examples: Vec<(String, String)>,
     user_prompt: String,
     timeout_secs: u64,
 ) -> Result<LLMResponse, String> {
     let start = Instant::now();
+    let request =
+        completion_request(model, system_prompt, &examples, &user_prompt, TEMPERATURE, None);
+    let response = send_completion(&base_url, &api_key, &request, timeout_secs).await?;
+    let (texts, tokens_used) = read_completion(response, &user_prompt).await?;
+    Ok(LLMResponse {
+        text: texts.into_iter().next().unwrap_or_default(),
+        tokens_used,
+        duration_ms: start.elapsed().as_millis() as u64,
+    })
+}
 
+/// `n` alternative completions. They are requested in one call with the
+/// `n` parameter; providers that reject `n` or return fewer choices get
+/// parallel single requests for the rest. Any other error is returned as is.
+#[allow(clippy::too_many_arguments)]
+pub async fn call_llm_candidates(
+    base_url: String,
+    api_key: String,
+    model: String,
+    system_prompt: String,
+    examples: Vec<(String, String)>,
+    user_prompt: String,
+    timeout_secs: u64,
+    n: usize,
+) -> Result<Candidates, String> {
+    let start = Instant::now();
+    let request = completion_request(
+        model.clone(),
+        system_prompt.clone(),
+        &examples,
+        &user_prompt,
+        CANDIDATE_TEMPERATURE,
+        Some(n as u32),
+    );
+    let response = send_completion(&base_url, &api_key, &request, timeout_secs).await?;
+    let status = response.status();
+    let (mut texts, mut tokens_used) = match status.as_u16() {
+        400 | 422 => {
+            // Only a complaint about `n` is worth retrying one by one; a bad
+            // model or an oversized prompt would just fail n times
+            let body = response.text().await.unwrap_or_default();
+            if !rejects_n(&body) {
+                return Err(error_message(status, &body));
+            }
+            (Vec::new(), None)
+        }
+        _ => read_completion(response, &user_prompt).await?,
+    };
+    texts.truncate(n);
+
+    let mut requests = JoinSet::new();
+    for _ in texts.len()..n {
+        let request = completion_request(
+            model.clone(),
+            system_prompt.clone(),
+            &examples,
+            &user_prompt,
+            CANDIDATE_TEMPERATURE,
+            None,
+        );
+        let (base_url, api_key) = (base_url.clone(), api_key.clone());
+        let user_prompt = user_prompt.clone();
+        requests.spawn(async move {
+            let response = send_completion(&base_url, &api_key, &request, timeout_secs).await?;
+            read_completion(response, &user_prompt).await
+        });
+    }
+    while let Some(result) = requests.join_next().await {
+        let (single, tokens) = result.map_err(|e| format!("Candidate request failed: {}", e))??;
+        texts.extend(single.into_iter().take(1));
+        tokens_used = match (tokens_used, tokens) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
+
+    Ok(Candidates {
+        texts,
+        tokens_used,
+        duration_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+/// System prompt, then each example as a user/assistant exchange, then the
+/// real input
+fn completion_request(
+    model: String,
+    system_prompt: String,
+    examples: &[(String, String)],
+    user_prompt: &str,
+    temperature: f32,
+    n: Option<u32>,
+) -> ChatCompletionRequest {
+    let mut messages = vec![ChatMessage {
+        role: "system".into(),
+        content: system_prompt,
+    }];
+    for (input, output) in examples {
+        messages.push(ChatMessage {
+            role: "user".into(),
+            content: input.clone(),
+        });
+        messages.push(ChatMessage {
+            role: "assistant".into(),
+            content: output.clone(),
+        });
+    }
+    messages.push(ChatMessage {
+        role: "user".into(),
+        content: user_prompt.to_string(),
+    });
+    ChatCompletionRequest {
+        model,
+        messages,
+        temperature,
+        max_tokens: Some(4096),
+        n,
+    }
+}
+
+/// Post a chat completion request; HTTP error statuses are left to the caller
+async fn send_completion(
+    base_url: &str,
+    api_key: &str,
+    request: &ChatCompletionRequest,
+    timeout_secs: u64,
+) -> Result<reqwest::Response, String> {
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(timeout_secs))
         .build()
@@ -21,7 +162,7 @@ pub async fn call_llm(
     let base = base_url.trim_end_matches('/');
     let url = if base.ends_with("/chat/completions") {
         base.to_string()
-    } else if base.split('/').last().map_or(false, |s| {
+    } else if base.split('/').next_back().is_some_and(|s| {
         s.starts_with('v') && s[1..].chars().all(|c| c.is_ascii_digit())
     }) {
         // Base URL already ends with a version path like /v1, /v4
@@ -30,27 +171,11 @@ pub async fn call_llm(
         format!("{}/v1/chat/completions", base)
     };
 
-    let request_body = ChatCompletionRequest {
-        model,
-        messages: vec![
-            ChatMessage {
-                role: "system".into(),
-                content: system_prompt,
-            },
-            ChatMessage {
-                role: "user".into(),
-                content: user_prompt.clone(),
-            },
-        ],
-        temperature: 0.3,
-        max_tokens: Some(4096),
-    };
-
-    let response = client
+    client
         .post(&url)
         .header("Authorization", format!("Bearer {}", api_key))
         .header("Content-Type", "application/json")
-        .json(&request_body)
+        .json(request)
         .send()
         .await
         .map_err(|e| {
@@ -61,48 +186,73 @@ pub async fn call_llm(
             } else {
                 format!("Request failed: {}", e)
             }
-        })?;
+        })
+}
 
+/// Every choice in a successful response, normalized against `user_prompt`,
+/// and the tokens used
+async fn read_completion(
+    response: reqwest::Response,
+    user_prompt: &str,
+) -> Result<(Vec<String>, Option<u64>), String> {
     let status = response.status();
     if !status.is_success() {
         let body = response.text().await.unwrap_or_default();
-        return Err(match status.as_u16() {
-            401 => "Invalid API key. Check your API key in settings.".into(),
-            403 => "Access denied. Your API key may not have permission for this model.".into(),
-            404 => format!("Model not found. Check the model name in settings. Response: {}", body),
-            429 => "Rate limited. Please wait and try again.".into(),
-            500..=599 => format!("Provider server error ({}): {}", status, body),
-            _ => format!("API error ({}): {}", status, body),
-        });
+        return Err(error_message(status, &body));
     }
 
-    let duration_ms = start.elapsed().as_millis() as u64;
-
     let completion: ChatCompletionResponse = response
         .json()
         .await
         .map_err(|e| format!("Failed to parse response: {}", e))?;
 
-    let raw_text = completion
-        .choices
-        .first()
-        .map(|c| c.message.content.clone())
-        .unwrap_or_default();
-
     let tokens_used = completion
         .usage
         .and_then(|u| u.total_tokens);
 
     // Extract original text from user prompt for normalization
     // The user_prompt contains the template with the original text
-    let normalized = response_normalizer::normalize(&raw_text, &user_prompt)
-        .unwrap_or(raw_text);
+    let texts = completion
+        .choices
+        .into_iter()
+        .map(|c| {
+            let raw_text = c.message.content;
+            response_normalizer::normalize(&raw_text, user_prompt).unwrap_or(raw_text)
+        })
+        .collect();
 
-    Ok(LLMResponse {
-        text: normalized,
-        tokens_used,
-        duration_ms,
-    })
+    Ok((texts, tokens_used))
+}
+
+fn error_message(status: reqwest::StatusCode, body: &str) -> String {
+    match status.as_u16() {
+        401 => "Invalid API key. Check your API key in settings.".into(),
+        403 => "Access denied. Your API key may not have permission for this model.".into(),
+        404 => format!("Model not found. Check the model name in settings. Response: {}", body),
+        429 => "Rate limited. Please wait and try again.".into(),
+        500..=599 => format!("Provider server error ({}): {}", status, body),
+        _ => format!("API error ({}): {}", status, body),
+    }
+}
+
+/// Whether an error body blames the `n` parameter, by the error's `param`
+/// or a message that names `n` or choices
+fn rejects_n(body: &str) -> bool {
+    let json: serde_json::Value = serde_json::from_str(body).unwrap_or_default();
+    let error = &json["error"];
+    if error["param"] == "n" {
+        return true;
+    }
+    let message = error["message"]
+        .as_str()
+        .or(error.as_str())
+        .or(json["message"].as_str())
+        .unwrap_or(body)
+        .to_lowercase();
+    message.contains("choices")
+        || message
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .any(|word| word == "n")
 }
 
 #[tauri::command]
@@ -118,6 +268,7 @@ pub async fn test_connection(
         api_key,
         model.clone(),
         "You are a test assistant.".into(),
+        Vec::new(),
         "Reply with exactly: OK".into(),
         10,
     )
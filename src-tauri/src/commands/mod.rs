@@ -3,4 +3,8 @@ pub mod llm_provider;
 pub mod keychain;
 pub mod hotkeys;
 pub mod history;
+pub mod preview;
+pub mod quick_input;
+pub mod rewrite;
 pub mod tray_icon;
+pub mod undo;
@@ -0,0 +1,234 @@
+use crate::commands::text_interaction::{paste_markdown, refocus_app, replace_text};
+use crate::commands::undo::record_replacement;
+use crate::services::text_backend::SharedBackend;
+use crate::services::text_diff::{self, DiffGranularity, DiffSpan};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State, WebviewUrl, WebviewWindowBuilder, WindowEvent};
+
+/// A rewrite result waiting for the user to accept, edit or reject it
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingReplacement {
+    pub request_id: String,
+    /// Plain text of the selection, recorded for undo
+    pub original_text: String,
+    /// The selection as shown and diffed in the panel; its Markdown when
+    /// `markdown` is set
+    pub display_original: String,
+    pub result_text: String,
+    pub diff: Vec<DiffSpan>,
+    /// Process id of the app that owned the selection when the preview opened
+    pub target_pid: Option<i32>,
+    /// The texts are Markdown and the result is pasted as rich text
+    pub markdown: bool,
+    /// Alternatives to choose between, `result_text` first; empty when
+    /// there is only the one result
+    pub candidates: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ReplacementResolved {
+    request_id: String,
+    accepted: bool,
+    /// Text that was pasted; `None` when the preview was rejected
+    text: Option<String>,
+    /// Why the replacement couldn't be pasted or the app refocused
+    error: Option<String>,
+}
+
+/// Replacements staged for preview, keyed by request id
+#[derive(Default)]
+pub struct PendingReplacements(Mutex<HashMap<String, PendingReplacement>>);
+
+impl PendingReplacements {
+    fn take(&self, request_id: &str) -> Result<PendingReplacement, String> {
+        self.0
+            .lock()
+            .map_err(|e| format!("Failed to lock pending replacements: {}", e))?
+            .remove(request_id)
+            .ok_or_else(|| format!("No pending replacement for request '{}'", request_id))
+    }
+}
+
+fn window_label(request_id: &str) -> String {
+    format!("preview-{}", request_id)
+}
+
+fn close_preview_window(app: &AppHandle, request_id: &str) {
+    if let Some(window) = app.get_webview_window(&window_label(request_id)) {
+        let _ = window.close();
+    }
+}
+
+/// Tell the frontend how a preview ended: the pasted text, `None` when
+/// rejected, or the error that stopped it
+fn emit_resolved(
+    app: &AppHandle,
+    request_id: String,
+    outcome: Result<Option<String>, String>,
+) -> Result<(), String> {
+    let (text, error) = match outcome {
+        Ok(text) => (text, None),
+        Err(e) => (None, Some(e)),
+    };
+    app.emit(
+        "replacement-resolved",
+        ReplacementResolved {
+            request_id,
+            accepted: text.is_some(),
+            text,
+            error,
+        },
+    )
+    .map_err(|e| format!("Failed to emit replacement-resolved: {}", e))
+}
+
+/// Stage a rewrite result and open a preview panel for it instead of pasting
+#[tauri::command]
+pub async fn stage_replacement(
+    app: AppHandle,
+    state: State<'_, PendingReplacements>,
+    original_text: String,
+    result_text: String,
+    markdown: Option<bool>,
+) -> Result<PendingReplacement, String> {
+    open_preview(
+        &app,
+        &state,
+        original_text.clone(),
+        original_text,
+        result_text,
+        markdown.unwrap_or(false),
+        Vec::new(),
+    )
+}
+
+/// Store a pending replacement and show it in a new preview window, with a
+/// chooser when there are `candidates`. `original_text` is what undo restores;
+/// `display_original` is what the result is compared against.
+pub fn open_preview(
+    app: &AppHandle,
+    state: &PendingReplacements,
+    original_text: String,
+    display_original: String,
+    result_text: String,
+    markdown: bool,
+    candidates: Vec<String>,
+) -> Result<PendingReplacement, String> {
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let pending = PendingReplacement {
+        request_id: request_id.clone(),
+        diff: text_diff::diff(&display_original, &result_text, DiffGranularity::Word),
+        original_text,
+        display_original,
+        result_text,
+        target_pid: app.state::<SharedBackend>().focused_app_pid(),
+        markdown,
+        candidates,
+    };
+
+    state
+        .0
+        .lock()
+        .map_err(|e| format!("Failed to lock pending replacements: {}", e))?
+        .insert(request_id.clone(), pending.clone());
+
+    let url = format!("index.html?view=preview&request={}", request_id);
+    let window =
+        WebviewWindowBuilder::new(app, window_label(&request_id), WebviewUrl::App(url.into()))
+            .title("Omni Text Preview")
+            .inner_size(440.0, if pending.candidates.is_empty() { 360.0 } else { 480.0 })
+            .resizable(false)
+            .decorations(false)
+            .transparent(true)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .center()
+            .focused(true)
+            .build();
+    let window = match window {
+        Ok(window) => window,
+        Err(e) => {
+            let _ = state.take(&request_id);
+            return Err(format!("Failed to open preview panel: {}", e));
+        }
+    };
+
+    // A panel closed any other way counts as rejected
+    let handle = app.clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::Destroyed = event {
+            let pending = handle.state::<PendingReplacements>();
+            if pending.take(&request_id).is_ok() {
+                let _ = emit_resolved(&handle, request_id.clone(), Ok(None));
+            }
+        }
+    });
+
+    Ok(pending)
+}
+
+#[tauri::command]
+pub fn get_pending_replacement(
+    state: State<'_, PendingReplacements>,
+    request_id: String,
+) -> Result<PendingReplacement, String> {
+    state
+        .0
+        .lock()
+        .map_err(|e| format!("Failed to lock pending replacements: {}", e))?
+        .get(&request_id)
+        .cloned()
+        .ok_or_else(|| format!("No pending replacement for request '{}'", request_id))
+}
+
+/// Paste the staged result, or the user's edited `text`, into the original app
+#[tauri::command]
+pub async fn accept_replacement(
+    app: AppHandle,
+    state: State<'_, PendingReplacements>,
+    request_id: String,
+    text: Option<String>,
+) -> Result<(), String> {
+    let pending = state.take(&request_id)?;
+    close_preview_window(&app, &request_id);
+    let pasted = paste_pending(&app, pending, text).await;
+    // The panel is gone, so a failure is reported through the event too
+    emit_resolved(&app, request_id, pasted.clone().map(Some))?;
+    pasted.map(|_| ())
+}
+
+/// Refocus the original app and paste; returns the text that landed there
+async fn paste_pending(
+    app: &AppHandle,
+    pending: PendingReplacement,
+    text: Option<String>,
+) -> Result<String, String> {
+    refocus_app(app, pending.target_pid).await?;
+
+    let text = text.unwrap_or(pending.result_text);
+    let pasted = if pending.markdown {
+        paste_markdown(app, &text)?
+    } else {
+        replace_text(app, &text)?;
+        text
+    };
+    // Report what landed in the app, in the same plain form as the original
+    record_replacement(app, pending.original_text, pasted.clone());
+    Ok(pasted)
+}
+
+/// Discard the staged result and leave the original selection untouched
+#[tauri::command]
+pub async fn reject_replacement(
+    app: AppHandle,
+    state: State<'_, PendingReplacements>,
+    request_id: String,
+) -> Result<(), String> {
+    let pending = state.take(&request_id)?;
+    close_preview_window(&app, &request_id);
+    let refocused = refocus_app(&app, pending.target_pid).await;
+    emit_resolved(&app, request_id, refocused.clone().map(|_| None))?;
+    refocused
+}
@@ -0,0 +1,152 @@
+use crate::models::{ActionInput, RewriteAction};
+use crate::services::action_inputs;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State, WebviewUrl, WebviewWindowBuilder, WindowEvent};
+use tokio::sync::oneshot;
+
+/// Error `run_action` returns when the user dismisses the quick-input panel
+pub const INPUT_CANCELLED: &str = "Cancelled";
+
+/// An action waiting for the user to fill in its inputs
+#[derive(Debug, Clone, Serialize)]
+pub struct InputRequest {
+    pub request_id: String,
+    pub action_name: String,
+    pub inputs: Vec<ActionInput>,
+}
+
+struct PendingInput {
+    request: InputRequest,
+    /// Resolves `ask_inputs` with the entered values, or `None` on cancel
+    reply: oneshot::Sender<Option<HashMap<String, String>>>,
+}
+
+/// Quick-input panels that are open, keyed by request id
+#[derive(Default)]
+pub struct PendingInputs(Mutex<HashMap<String, PendingInput>>);
+
+impl PendingInputs {
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, PendingInput>>, String> {
+        self.0
+            .lock()
+            .map_err(|e| format!("Failed to lock pending inputs: {}", e))
+    }
+
+    fn take(&self, request_id: &str) -> Result<PendingInput, String> {
+        self.lock()?
+            .remove(request_id)
+            .ok_or_else(|| format!("No input request '{}'", request_id))
+    }
+}
+
+fn window_label(request_id: &str) -> String {
+    format!("input-{}", request_id)
+}
+
+fn close_input_window(app: &AppHandle, request_id: &str) {
+    if let Some(window) = app.get_webview_window(&window_label(request_id)) {
+        let _ = window.close();
+    }
+}
+
+/// Show the quick-input panel for `action` and wait for the user. Returns the
+/// values for every input, or `None` when the panel was cancelled or closed.
+pub async fn ask_inputs(
+    app: &AppHandle,
+    action: &RewriteAction,
+) -> Result<Option<HashMap<String, String>>, String> {
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let (reply, response) = oneshot::channel();
+    let request = InputRequest {
+        request_id: request_id.clone(),
+        action_name: action.name.clone(),
+        inputs: action.inputs.clone(),
+    };
+    app.state::<PendingInputs>()
+        .lock()?
+        .insert(request_id.clone(), PendingInput { request, reply });
+
+    let url = format!("index.html?view=input&request={}", request_id);
+    let window =
+        WebviewWindowBuilder::new(app, window_label(&request_id), WebviewUrl::App(url.into()))
+            .title("Omni Text")
+            .inner_size(360.0, 120.0 + 56.0 * action.inputs.len() as f64)
+            .resizable(false)
+            .decorations(false)
+            .transparent(true)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .center()
+            .focused(true)
+            .build();
+    let window = match window {
+        Ok(window) => window,
+        Err(e) => {
+            let _ = app.state::<PendingInputs>().take(&request_id);
+            return Err(format!("Failed to open input panel: {}", e));
+        }
+    };
+
+    // A panel closed any other way counts as cancelled
+    let handle = app.clone();
+    let id = request_id.clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::Destroyed = event {
+            if let Ok(pending) = handle.state::<PendingInputs>().take(&id) {
+                let _ = pending.reply.send(None);
+            }
+        }
+    });
+
+    Ok(response.await.unwrap_or(None))
+}
+
+#[tauri::command]
+pub fn get_input_request(
+    state: State<'_, PendingInputs>,
+    request_id: String,
+) -> Result<InputRequest, String> {
+    state
+        .lock()?
+        .get(&request_id)
+        .map(|pending| pending.request.clone())
+        .ok_or_else(|| format!("No input request '{}'", request_id))
+}
+
+/// Run the waiting action with `values`; the panel stays open if one is invalid
+#[tauri::command]
+pub fn submit_inputs(
+    app: AppHandle,
+    state: State<'_, PendingInputs>,
+    request_id: String,
+    values: HashMap<String, String>,
+) -> Result<(), String> {
+    let mut pending = state.lock()?;
+    let request = &pending
+        .get(&request_id)
+        .ok_or_else(|| format!("No input request '{}'", request_id))?
+        .request;
+    let values = action_inputs::resolve_values(&request.inputs, &values)?;
+    if let Some(entry) = pending.remove(&request_id) {
+        let _ = entry.reply.send(Some(values));
+    }
+    // Closing fires the Destroyed handler, which needs the lock
+    drop(pending);
+    close_input_window(&app, &request_id);
+    Ok(())
+}
+
+/// Dismiss the panel without running the action
+#[tauri::command]
+pub fn cancel_inputs(
+    app: AppHandle,
+    state: State<'_, PendingInputs>,
+    request_id: String,
+) -> Result<(), String> {
+    let pending = state.take(&request_id)?;
+    let _ = pending.reply.send(None);
+    close_input_window(&app, &request_id);
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use crate::commands::history::SETTINGS_STORE;
+use crate::commands::preview::{open_preview, PendingReplacement, PendingReplacements};
+use crate::commands::quick_input::{ask_inputs, INPUT_CANCELLED};
+use crate::commands::text_interaction::{clipboard_restore_delay, refocus_app};
+use crate::commands::undo::record_replacement;
+use crate::models::{
+    AppContext, ProviderConfig, ReplaceMode, RewriteAction, StepOutput, TemplateVariable,
+};
+use crate::services::app_rules::{self, DEFAULT_SECURE_APPS};
+use crate::services::key_storage::KeyStorage;
+use crate::services::rewrite_pipeline::{self, RewriteSettings};
+use crate::services::{action_inputs, markdown, template};
+use crate::services::text_backend::SharedBackend;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_store::StoreExt;
+
+const SECURE_APPS_KEY: &str = "secureApps";
+const TEMPLATE_VARIABLES_KEY: &str = "templateVariables";
+const ACTIONS_KEY: &str = "actions";
+const PROVIDERS_KEY: &str = "providers";
+
+/// Outcome of running an action, with what the frontend needs for history
+#[derive(Debug, Clone, Serialize)]
+pub struct RewriteResult {
+    pub original_text: String,
+    pub result_text: String,
+    pub duration_ms: u64,
+    pub tokens_used: Option<u64>,
+    /// Application the selection was read from
+    pub app: Option<AppContext>,
+    /// Set when the action previews its result; nothing was pasted yet
+    pub pending: Option<PendingReplacement>,
+    /// Intermediate outputs of a multi-step action
+    pub steps: Vec<StepOutput>,
+    /// Every alternative generated, `result_text` first
+    pub candidates: Vec<String>,
+}
+
+/// Rewrite the selection in the frontmost app with `action`: ask for its
+/// inputs if it has any, read the selection, call the provider, then paste the
+/// result or stage it for preview
+#[tauri::command]
+pub async fn run_action(
+    app: AppHandle,
+    action: RewriteAction,
+    provider: ProviderConfig,
+) -> Result<RewriteResult, String> {
+    // Multi-step actions can use other actions and providers
+    let steps = {
+        let keys = app.state::<KeyStorage>();
+        rewrite_pipeline::resolve_steps(
+            &action,
+            &saved_setting::<Vec<RewriteAction>>(&app, ACTIONS_KEY).unwrap_or_default(),
+            &saved_setting::<Vec<ProviderConfig>>(&app, PROVIDERS_KEY).unwrap_or_default(),
+            &provider,
+            |p| keys.get(&p.name),
+        )?
+    };
+    let backend = app.state::<SharedBackend>().inner().clone();
+    let mut settings = rewrite_settings(&app);
+
+    if !action.inputs.is_empty() {
+        // Don't ask for inputs in an app the action can't run in
+        let frontmost = backend.frontmost_app();
+        app_rules::check_secure_app(&settings.secure_apps, frontmost.as_ref())?;
+        app_rules::check_allowed(&action, frontmost.as_ref())?;
+
+        // The panel takes focus; give it back before reading the selection
+        let target_pid = backend.focused_app_pid();
+        settings.inputs = ask_inputs(&app, &action).await?.ok_or(INPUT_CANCELLED)?;
+        refocus_app(&app, target_pid).await?;
+    }
+
+    let rewrite =
+        rewrite_pipeline::rewrite_selection(backend.as_ref(), &action, &steps, &settings).await?;
+
+    // Several candidates always need the user to pick one
+    let preview = action.replace_mode == ReplaceMode::Preview || rewrite.candidates.len() > 1;
+    let pending = if preview {
+        Some(open_preview(
+            &app,
+            &app.state::<PendingReplacements>(),
+            rewrite.original_text.clone(),
+            rewrite.input_text.clone(),
+            rewrite.response.text.clone(),
+            rewrite.markdown,
+            rewrite.candidates.clone(),
+        )?)
+    } else {
+        let pasted =
+            rewrite_pipeline::apply_rewrite(backend.as_ref(), &rewrite, clipboard_restore_delay(&app))?;
+        record_replacement(&app, rewrite.original_text.clone(), pasted);
+        None
+    };
+
+    // History compares the result with the plain-text original, so Markdown
+    // results are reported as the plain text they paste as
+    let plain = |text: &String| {
+        if rewrite.markdown {
+            markdown::markdown_to_plain(text)
+        } else {
+            text.clone()
+        }
+    };
+    Ok(RewriteResult {
+        result_text: plain(&rewrite.response.text),
+        candidates: rewrite.candidates.iter().map(plain).collect(),
+        original_text: rewrite.original_text,
+        duration_ms: rewrite.response.duration_ms,
+        tokens_used: rewrite.response.tokens_used,
+        app: rewrite.app,
+        pending,
+        steps: rewrite.steps,
+    })
+}
+
+/// Check the action's inputs and templates, including per-app ones, before it
+/// is saved
+#[tauri::command]
+pub fn validate_action(
+    action: RewriteAction,
+    variables: Vec<TemplateVariable>,
+) -> Result<(), String> {
+    action_inputs::validate(&action.inputs)?;
+    let names: Vec<String> = variables
+        .into_iter()
+        .map(|v| v.name)
+        .chain(action.inputs.iter().map(|input| input.name.clone()))
+        .collect();
+    template::validate(&action.user_template, &names)
+        .map_err(|e| format!("User template: {}", e))?;
+    for o in &action.app_overrides {
+        if let Some(user_template) = &o.user_template {
+            template::validate(user_template, &names)
+                .map_err(|e| format!("User template for {}: {}", o.app, e))?;
+        }
+    }
+    for (i, step) in action.steps.iter().enumerate() {
+        if step.action_id.is_none() {
+            template::validate(&step.user_template, &names)
+                .map_err(|e| format!("Step {} template: {}", i + 1, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Secure apps from the Privacy tab and template variables from Settings
+fn rewrite_settings(app: &AppHandle) -> RewriteSettings {
+    RewriteSettings {
+        secure_apps: saved_setting(app, SECURE_APPS_KEY)
+            .unwrap_or_else(|| DEFAULT_SECURE_APPS.iter().map(|s| s.to_string()).collect()),
+        variables: saved_setting(app, TEMPLATE_VARIABLES_KEY).unwrap_or_default(),
+        inputs: Default::default(),
+    }
+}
+
+/// A value the frontend saved in the settings store
+fn saved_setting<T: DeserializeOwned>(app: &AppHandle, key: &str) -> Option<T> {
+    app.store(SETTINGS_STORE)
+        .ok()
+        .and_then(|store| store.get(key))
+        .and_then(|value| serde_json::from_value(value).ok())
+}
@@ -1,68 +1,92 @@
-use crate::services::{accessibility, clipboard};
-use tauri_plugin_clipboard_manager::ClipboardExt;
+use crate::commands::history::SETTINGS_STORE;
+use crate::commands::undo::record_replacement;
+use crate::models::SelectedText;
+use crate::services::selection;
+use crate::services::text_backend::SharedBackend;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_store::StoreExt;
 
-#[tauri::command]
-pub async fn get_selected_text(app: tauri::AppHandle) -> Result<String, String> {
-    // Check if focused field is secure
-    if accessibility::is_secure_field() {
-        return Err("Cannot read from secure/password fields".into());
-    }
-
-    // Try accessibility API first
-    match accessibility::get_selected_text_ax() {
-        Ok(text) if !text.is_empty() => return Ok(text),
-        _ => {}
-    }
-
-    // Fall back to clipboard simulation
-    // Save current clipboard contents
-    let saved_clipboard = app
-        .clipboard()
-        .read_text()
-        .ok();
+/// Give a re-activated app time to become key again before sending keystrokes
+const REFOCUS_DELAY: Duration = Duration::from_millis(150);
+const RESTORE_DELAY_KEY: &str = "clipboardRestoreDelayMs";
+/// Some apps read the pasteboard lazily after Cmd+V, so restoring too early
+/// pastes the user's old clipboard instead of the rewrite
+const DEFAULT_RESTORE_DELAY_MS: u64 = 300;
 
-    // Simulate Cmd+C
-    clipboard::simulate_copy()
-        .map_err(|e| format!("Failed to simulate copy: {}", e))?;
-
-    // Read the new clipboard content
-    let selected = app
-        .clipboard()
-        .read_text()
-        .map_err(|e| format!("Failed to read clipboard: {}", e))?;
+#[tauri::command]
+pub async fn get_selected_text(backend: State<'_, SharedBackend>) -> Result<String, String> {
+    selection::read_selected_text(backend.inner().as_ref())
+}
 
-    // Restore original clipboard
-    if let Some(saved) = saved_clipboard {
-        let _ = app.clipboard().write_text(saved);
-    }
+/// Copy the selection with its HTML/RTF flavor so formatting can be sent to
+/// the LLM as Markdown
+#[tauri::command]
+pub async fn get_selected_rich_text(
+    backend: State<'_, SharedBackend>,
+) -> Result<SelectedText, String> {
+    selection::read_rich_selection(backend.inner().as_ref())
+}
 
-    if !selected.is_empty() {
-        Ok(selected)
+/// Replace the selection with `text`. With `markdown`, `text` is the LLM's
+/// Markdown and is pasted as HTML with a plain-text alternative.
+#[tauri::command]
+pub async fn replace_selected_text(
+    app: AppHandle,
+    text: String,
+    original_text: Option<String>,
+    markdown: Option<bool>,
+) -> Result<(), String> {
+    let pasted = if markdown.unwrap_or(false) {
+        paste_markdown(&app, &text)?
     } else {
-        Err("No text selected".into())
+        replace_text(&app, &text)?;
+        text
+    };
+    if let Some(original_text) = original_text {
+        record_replacement(&app, original_text, pasted);
     }
+    Ok(())
 }
 
-#[tauri::command]
-pub async fn replace_selected_text(app: tauri::AppHandle, text: String) -> Result<(), String> {
-    // Write replacement text to clipboard
-    app.clipboard()
-        .write_text(&text)
-        .map_err(|e| format!("Failed to write to clipboard: {}", e))?;
+/// Replace the current selection in the frontmost app with `text`, directly
+/// through the backend where possible and by pasting otherwise
+pub fn replace_text(app: &AppHandle, text: &str) -> Result<(), String> {
+    let backend = app.state::<SharedBackend>();
+    selection::replace_text(backend.inner().as_ref(), text, clipboard_restore_delay(app))
+}
 
-    // Simulate Cmd+V to paste
-    clipboard::simulate_paste()
-        .map_err(|e| format!("Failed to simulate paste: {}", e))?;
+/// Paste Markdown as rich text; returns the plain-text flavor that was pasted
+pub fn paste_markdown(app: &AppHandle, text: &str) -> Result<String, String> {
+    let backend = app.state::<SharedBackend>();
+    selection::paste_markdown(backend.inner().as_ref(), text, clipboard_restore_delay(app))
+}
+
+pub fn clipboard_restore_delay(app: &AppHandle) -> Duration {
+    let millis = app
+        .store(SETTINGS_STORE)
+        .ok()
+        .and_then(|store| store.get(RESTORE_DELAY_KEY))
+        .and_then(|value| value.as_u64())
+        .unwrap_or(DEFAULT_RESTORE_DELAY_MS);
+    Duration::from_millis(millis)
+}
 
+/// Bring the app with `pid` back to the front, if one was captured
+pub async fn refocus_app(app: &AppHandle, pid: Option<i32>) -> Result<(), String> {
+    if let Some(pid) = pid {
+        app.state::<SharedBackend>().activate_app(pid)?;
+        tokio::time::sleep(REFOCUS_DELAY).await;
+    }
     Ok(())
 }
 
 #[tauri::command]
-pub fn check_accessibility_permission() -> bool {
-    accessibility::check_permission()
+pub fn check_accessibility_permission(backend: State<'_, SharedBackend>) -> bool {
+    backend.has_permission()
 }
 
 #[tauri::command]
-pub fn request_accessibility_permission() {
-    accessibility::request_permission();
+pub fn request_accessibility_permission(backend: State<'_, SharedBackend>) {
+    backend.request_permission();
 }
@@ -0,0 +1,71 @@
+use crate::commands::text_interaction::{refocus_app, replace_text};
+use crate::services::text_backend::SharedBackend;
+use crate::services::undo_stack::{UndoEntry, UndoStack};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UndoOutcome {
+    /// The rewrite was re-selected and the original text pasted back
+    Restored,
+    /// Re-selection failed, so the original text was put on the clipboard
+    Copied,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct UndoCompleted {
+    outcome: Option<UndoOutcome>,
+    error: Option<String>,
+}
+
+/// Remember a replacement that was just pasted into the frontmost app
+pub fn record_replacement(app: &AppHandle, original_text: String, result_text: String) {
+    if let Some(stack) = app.try_state::<UndoStack>() {
+        stack.push(UndoEntry {
+            original_text,
+            result_text,
+            target_pid: app.state::<SharedBackend>().focused_app_pid(),
+        });
+    }
+}
+
+async fn restore(app: &AppHandle, entry: UndoEntry) -> Result<UndoOutcome, String> {
+    // The app may have quit since, or was never known; fall through to the
+    // clipboard rather than paste into whatever has focus now
+    let refocused = match entry.target_pid {
+        Some(pid) => refocus_app(app, Some(pid)).await.is_ok(),
+        None => false,
+    };
+    let backend = app.state::<SharedBackend>();
+
+    if refocused && backend.select_text(&entry.result_text).is_ok() {
+        replace_text(app, &entry.original_text)?;
+        return Ok(UndoOutcome::Restored);
+    }
+
+    backend.write_clipboard_text(&entry.original_text)?;
+    Ok(UndoOutcome::Copied)
+}
+
+/// Undo the most recent rewrite and notify the frontend of the outcome
+pub async fn undo_last(app: &AppHandle) -> Result<UndoOutcome, String> {
+    let result = match app.state::<UndoStack>().pop() {
+        Some(entry) => restore(app, entry).await,
+        None => Err("Nothing to undo".into()),
+    };
+
+    let _ = app.emit(
+        "undo-completed",
+        UndoCompleted {
+            outcome: result.as_ref().ok().copied(),
+            error: result.as_ref().err().cloned(),
+        },
+    );
+    result
+}
+
+#[tauri::command]
+pub async fn undo_last_rewrite(app: AppHandle) -> Result<UndoOutcome, String> {
+    undo_last(&app).await
+}
@@ -10,6 +10,7 @@ use tauri::{
     AppHandle, Manager,
 };
 use tauri_plugin_positioner::{Position, WindowExt as PosWindowExt};
+#[cfg(target_os = "macos")]
 use window_vibrancy::{apply_vibrancy, NSVisualEffectMaterial, NSVisualEffectState};
 
 use commands::{
@@ -17,8 +18,12 @@ use commands::{
     hotkeys::*,
     keychain::*,
     llm_provider::*,
+    preview::*,
+    quick_input::*,
+    rewrite::*,
     text_interaction::*,
     tray_icon::*,
+    undo::*,
 };
 
 #[tauri::command]
@@ -32,36 +37,15 @@ pub fn run() {
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .plugin(tauri_plugin_positioner::init())
         .plugin(tauri_plugin_store::Builder::new().build())
-        .plugin(
-            tauri_plugin_sql::Builder::default()
-                .add_migrations(
-                    "sqlite:omni_text_history.db",
-                    vec![tauri_plugin_sql::Migration {
-                        version: 1,
-                        description: "create history table",
-                        sql: "CREATE TABLE IF NOT EXISTS history (
-                            id TEXT PRIMARY KEY,
-                            timestamp TEXT NOT NULL,
-                            action_name TEXT NOT NULL,
-                            app_name TEXT NOT NULL DEFAULT '',
-                            original_text TEXT NOT NULL,
-                            result_text TEXT NOT NULL,
-                            provider TEXT NOT NULL,
-                            model TEXT NOT NULL,
-                            duration_ms INTEGER NOT NULL DEFAULT 0,
-                            tokens_used INTEGER
-                        )",
-                        kind: tauri_plugin_sql::MigrationKind::Up,
-                    }],
-                )
-                .build(),
-        )
         .plugin(tauri_plugin_clipboard_manager::init())
         .invoke_handler(tauri::generate_handler![
             get_selected_text,
+            get_selected_rich_text,
             replace_selected_text,
             check_accessibility_permission,
             request_accessibility_permission,
+            run_action,
+            validate_action,
             call_llm,
             test_connection,
             save_api_key,
@@ -75,6 +59,19 @@ pub fn run() {
             search_history,
             delete_history_entry,
             clear_history,
+            export_history,
+            import_history,
+            prune_history,
+            promote_history_example,
+            diff_texts,
+            stage_replacement,
+            get_pending_replacement,
+            accept_replacement,
+            reject_replacement,
+            get_input_request,
+            submit_inputs,
+            cancel_inputs,
+            undo_last_rewrite,
             quit_app,
             set_tray_state,
         ])
@@ -82,23 +79,49 @@ pub fn run() {
             // Initialize encrypted key storage
             let app_dir = app.path().app_config_dir().expect("Failed to get app config dir");
             std::fs::create_dir_all(&app_dir).expect("Failed to create app config dir");
-            let key_storage = services::key_storage::KeyStorage::new(app_dir);
+            let key_storage = services::key_storage::KeyStorage::new(app_dir.clone());
             app.manage(key_storage);
 
+            // Open the history database and apply pending migrations
+            let history_cipher =
+                services::key_storage::machine_cipher(services::key_storage::HISTORY_INFO)
+                    .expect("Failed to derive history encryption key");
+            let history_store =
+                services::history_store::HistoryStore::open(app_dir, history_cipher)
+                    .expect("Failed to open history database");
+            app.manage(history_store);
+            app.manage(commands::preview::PendingReplacements::default());
+            app.manage(commands::quick_input::PendingInputs::default());
+            app.manage(services::undo_stack::UndoStack::default());
+
+            // Platform text interaction backend, picked at compile time
+            let text_backend: services::text_backend::SharedBackend = Arc::new(
+                services::text_backend::PlatformBackend::new(app.handle().clone()),
+            );
+            app.manage(text_backend);
+            commands::history::spawn_retention_task(app.handle().clone());
+
             // Hide from Dock — menu bar only app
             #[cfg(target_os = "macos")]
             app.set_activation_policy(tauri::ActivationPolicy::Accessory);
 
             // Apply vibrancy to main window
+            #[cfg(target_os = "macos")]
             if let Some(window) = app.get_webview_window("main") {
-                #[cfg(target_os = "macos")]
                 let _ = apply_vibrancy(&window, NSVisualEffectMaterial::HudWindow, Some(NSVisualEffectState::Active), None);
             }
 
             // Build tray icon
+            let undo_item = MenuItem::with_id(
+                app,
+                "undo",
+                "Undo Last Omni Text Rewrite",
+                true,
+                None::<&str>,
+            )?;
             let quit_item =
                 MenuItem::with_id(app, "quit", "Quit Omni Text", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&quit_item])?;
+            let menu = Menu::with_items(app, &[&undo_item, &quit_item])?;
 
             // Load cat-ready icon for initial tray state
             let ready_icon = tauri::image::Image::from_bytes(
@@ -112,6 +135,12 @@ pub fn run() {
                 .menu(&menu)
                 .show_menu_on_left_click(false)
                 .on_menu_event(|app, event| match event.id.as_ref() {
+                    "undo" => {
+                        let app = app.clone();
+                        tauri::async_runtime::spawn(async move {
+                            let _ = commands::undo::undo_last(&app).await;
+                        });
+                    }
                     "quit" => {
                         app.exit(0);
                     }
@@ -8,6 +8,32 @@ pub enum AppStatus {
     NotReady,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(dead_code)]
+pub enum ReplaceMode {
+    /// Paste the result as soon as it arrives
+    #[default]
+    Immediate,
+    /// Show the result in a preview panel and paste only on accept
+    Preview,
+}
+
+/// What an action works on when nothing is selected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FallbackScope {
+    /// Fail with "No text selected"
+    #[default]
+    None,
+    /// Everything in the focused field
+    Field,
+    /// The line the caret is on
+    Line,
+    /// The run of non-blank lines around the caret
+    Paragraph,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct RewriteAction {
@@ -18,18 +44,165 @@ pub struct RewriteAction {
     pub user_template: String,
     pub output_rules: String,
     pub enabled: bool,
+    #[serde(default)]
+    pub replace_mode: ReplaceMode,
+    /// Round-trip bold, links and lists through Markdown instead of plain text
+    #[serde(default)]
+    pub preserve_formatting: bool,
+    /// Apps (name or bundle id) the action is limited to; empty means all
+    #[serde(default)]
+    pub include_apps: Vec<String>,
+    /// Apps the action never runs in
+    #[serde(default)]
+    pub exclude_apps: Vec<String>,
+    /// Prompt changes for specific apps; the first matching entry wins
+    #[serde(default)]
+    pub app_overrides: Vec<AppPromptOverride>,
+    /// Characters read on each side of the selection for
+    /// `{{context_before}}`/`{{context_after}}`; `None` uses the default
+    #[serde(default)]
+    pub context_chars: Option<usize>,
+    #[serde(default)]
+    pub fallback_scope: FallbackScope,
+    /// Values asked for in the quick-input panel each time the action runs
+    #[serde(default)]
+    pub inputs: Vec<ActionInput>,
+    /// Run these in order instead of the action's own prompts, each one
+    /// rewriting the previous step's output
+    #[serde(default)]
+    pub steps: Vec<ActionStep>,
+    /// Alternatives to generate and pick from; `None` or 1 uses the only one
+    #[serde(default)]
+    pub candidates: Option<usize>,
+    /// Input/output pairs sent ahead of the real input as earlier turns of
+    /// the conversation. Each input goes through the user template as
+    /// `{{text}}`, so it reads like the real turn.
+    #[serde(default)]
+    pub examples: Vec<(String, String)>,
+}
+
+/// One step of a multi-step action: another action's prompts when
+/// `action_id` is set, otherwise the step's own
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ActionStep {
+    #[serde(default)]
+    pub action_id: Option<String>,
+    /// Shown in history; blank uses the referenced action's name
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub system_prompt: String,
+    #[serde(default)]
+    pub user_template: String,
+    #[serde(default)]
+    pub output_rules: String,
+    /// Provider id from the Providers tab; `None` uses the active provider
+    #[serde(default)]
+    pub provider_id: Option<String>,
+    /// Model to use instead of the provider's
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// What one step of a multi-step action produced
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StepOutput {
+    pub name: String,
+    pub provider: String,
+    pub model: String,
+    pub text: String,
+    pub duration_ms: u64,
+    pub tokens_used: Option<u64>,
+}
+
+/// Kind of value an action input asks for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionInputKind {
+    /// Free text
+    #[default]
+    Text,
+    /// One of the input's `options`
+    Choice,
+}
+
+/// A parameter such as "target language", filled in when the action is
+/// triggered and used in the template as `{{name}}`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActionInput {
+    pub name: String,
+    /// Shown in the quick-input panel; blank uses the name
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub kind: ActionInputKind,
+    #[serde(default)]
+    pub options: Vec<String>,
+    /// Pre-filled value; for choices, blank picks the first option
+    #[serde(default)]
+    pub default: String,
+}
+
+/// Prompt fields replaced when an action runs in a specific app. `None`
+/// keeps the action's own value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppPromptOverride {
+    /// App name or bundle id
+    pub app: String,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub user_template: Option<String>,
+    #[serde(default)]
+    pub output_rules: Option<String>,
+}
+
+/// A user-defined `{{name}}` for prompt templates, e.g. a signature
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TemplateVariable {
+    pub name: String,
+    pub value: String,
+}
+
+/// Text around the selection in the focused field
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SelectionContext {
+    pub before: String,
+    pub after: String,
+}
+
+/// The application a selection was read from
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AppContext {
+    pub name: String,
+    /// Bundle identifier on macOS, WM_CLASS on X11
+    pub bundle_id: Option<String>,
+    pub window_title: Option<String>,
+}
+
+/// A selection captured through the clipboard, with its rich-text flavor
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectedText {
+    pub text: String,
+    /// Markdown converted from the copied HTML/RTF; `None` for plain-text sources
+    pub markdown: Option<String>,
+    /// Frontmost application when the selection was read
+    pub app: Option<AppContext>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct ProviderConfig {
+    /// Set by the frontend; older saved providers don't have one
+    #[serde(default)]
+    pub id: String,
     pub name: String,
     pub base_url: String,
     pub model: String,
     pub timeout_secs: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct HistoryEntry {
     pub id: String,
     pub timestamp: String,
@@ -41,6 +214,78 @@ pub struct HistoryEntry {
     pub model: String,
     pub duration_ms: u64,
     pub tokens_used: Option<u64>,
+    /// Word-level change hunks between original and result, computed on insert
+    #[serde(default)]
+    pub changes_count: Option<u32>,
+    #[serde(default)]
+    pub app_bundle_id: Option<String>,
+    /// Stored encrypted, like the text columns
+    #[serde(default)]
+    pub window_title: Option<String>,
+    /// Intermediate outputs of a multi-step action, also stored encrypted
+    #[serde(default)]
+    pub steps: Vec<StepOutput>,
+    /// Every alternative offered when the action generates several, also
+    /// stored encrypted
+    #[serde(default)]
+    pub candidates: Vec<String>,
+}
+
+/// Structured history filters. Every field is optional; set fields are ANDed.
+/// `from`/`to` are RFC 3339 timestamps and bound the range inclusively.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryFilter {
+    pub query: Option<String>,
+    pub action_name: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub app_name: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub min_duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryExportFormat {
+    Jsonl,
+    Csv,
+    Markdown,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HistoryImportReport {
+    pub inserted: usize,
+    pub skipped: usize,
+    pub invalid: usize,
+}
+
+/// History retention limits; `None` disables a limit
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionSettings {
+    pub max_age_days: Option<u32>,
+    pub max_rows: Option<u64>,
+    pub max_db_size_mb: Option<u64>,
+}
+
+/// Rows removed by each retention limit. On a dry run nothing is deleted and
+/// the counts describe what would be removed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    pub removed_by_age: usize,
+    pub removed_by_count: usize,
+    pub removed_by_size: usize,
+    pub db_size_bytes: u64,
+    pub vacuumed: bool,
+}
+
+impl PruneReport {
+    pub fn total_removed(&self) -> usize {
+        self.removed_by_age + self.removed_by_count + self.removed_by_size
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -63,7 +308,8 @@ pub struct ConnectionTestResult {
 pub enum TextInteractionError {
     AccessibilityDenied,
     NoSelection,
-    SecureField,
+    /// Carries the reason the field counts as secure
+    SecureField(String),
     ClipboardError(String),
     Unknown(String),
 }
@@ -73,7 +319,7 @@ impl std::fmt::Display for TextInteractionError {
         match self {
             Self::AccessibilityDenied => write!(f, "Accessibility permission not granted"),
             Self::NoSelection => write!(f, "No text selected"),
-            Self::SecureField => write!(f, "Cannot interact with secure/password fields"),
+            Self::SecureField(reason) => write!(f, "Cannot read secure input: {}", reason),
             Self::ClipboardError(msg) => write!(f, "Clipboard error: {}", msg),
             Self::Unknown(msg) => write!(f, "Unknown error: {}", msg),
         }
@@ -87,6 +333,9 @@ pub struct ChatCompletionRequest {
     pub messages: Vec<ChatMessage>,
     pub temperature: f32,
     pub max_tokens: Option<u32>,
+    /// Number of choices; left out for providers that don't know it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1,55 +1,158 @@
+use crate::models::SelectionContext;
+use crate::services::cf::{CFRange, CFString, CFType, CFTypeID, CFTypeRef};
+use crate::services::text_backend::clip_context;
 use std::ffi::c_void;
+use std::ops::Range;
 
-type CFBooleanRef = *const c_void;
-type CFStringRef = *const c_void;
-type CFDictionaryRef = *const c_void;
-type AXUIElementRef = *const c_void;
 type AXError = i32;
-type CFTypeRef = *const c_void;
 
 const K_AX_ERROR_SUCCESS: AXError = 0;
+const K_AX_VALUE_CF_RANGE_TYPE: u32 = 4;
 
 #[link(name = "ApplicationServices", kind = "framework")]
 extern "C" {
-    fn AXIsProcessTrustedWithOptions(options: CFDictionaryRef) -> bool;
-    fn AXUIElementCreateSystemWide() -> AXUIElementRef;
+    fn AXIsProcessTrustedWithOptions(options: CFTypeRef) -> bool;
+    fn AXUIElementCreateSystemWide() -> CFTypeRef;
+    fn AXUIElementCreateApplication(pid: i32) -> CFTypeRef;
     fn AXUIElementCopyAttributeValue(
-        element: AXUIElementRef,
-        attribute: CFStringRef,
+        element: CFTypeRef,
+        attribute: CFTypeRef,
         value: *mut CFTypeRef,
     ) -> AXError;
+    fn AXUIElementSetAttributeValue(
+        element: CFTypeRef,
+        attribute: CFTypeRef,
+        value: CFTypeRef,
+    ) -> AXError;
+    fn AXUIElementGetPid(element: CFTypeRef, pid: *mut i32) -> AXError;
+    fn AXValueCreate(the_type: u32, value_ptr: *const c_void) -> CFTypeRef;
+    fn AXValueGetValue(value: CFTypeRef, the_type: u32, value_ptr: *mut c_void) -> bool;
+    fn AXValueGetTypeID() -> CFTypeID;
+    fn AXValueGetType(value: CFTypeRef) -> u32;
 }
 
 #[link(name = "CoreFoundation", kind = "framework")]
 extern "C" {
-    static kCFBooleanTrue: CFBooleanRef;
+    static kCFBooleanTrue: CFTypeRef;
     static kCFTypeDictionaryKeyCallBacks: c_void;
     static kCFTypeDictionaryValueCallBacks: c_void;
-    fn CFStringCreateWithCString(
-        alloc: *const c_void,
-        c_str: *const u8,
-        encoding: u32,
-    ) -> CFStringRef;
     fn CFDictionaryCreate(
         allocator: *const c_void,
-        keys: *const *const c_void,
-        values: *const *const c_void,
+        keys: *const CFTypeRef,
+        values: *const CFTypeRef,
         num_values: isize,
         key_callbacks: *const c_void,
         value_callbacks: *const c_void,
-    ) -> CFDictionaryRef;
-    fn CFRelease(cf: *const c_void);
+    ) -> CFTypeRef;
 }
 
-const K_CF_STRING_ENCODING_UTF8: u32 = 0x08000100;
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    /// Carbon `Boolean`, nonzero while any process has secure input enabled
+    fn IsSecureEventInputEnabled() -> u8;
+}
 
-fn cf_string(s: &str) -> CFStringRef {
-    unsafe {
-        let c_str = std::ffi::CString::new(s).unwrap();
-        CFStringCreateWithCString(std::ptr::null(), c_str.as_ptr() as *const u8, K_CF_STRING_ENCODING_UTF8)
+/// An accessibility element with typed attribute access
+struct AXElement(CFType);
+
+impl AXElement {
+    fn system_wide() -> Self {
+        let element = unsafe { CFType::wrap(AXUIElementCreateSystemWide()) };
+        Self(element.expect("AXUIElementCreateSystemWide returned null"))
+    }
+
+    fn application(pid: i32) -> Self {
+        let element = unsafe { CFType::wrap(AXUIElementCreateApplication(pid)) };
+        Self(element.expect("AXUIElementCreateApplication returned null"))
+    }
+
+    /// Raw attribute value; `None` when the element doesn't have it
+    fn attribute(&self, name: &str) -> Option<CFType> {
+        let attr = CFString::new(name);
+        let mut value: CFTypeRef = std::ptr::null();
+        let err =
+            unsafe { AXUIElementCopyAttributeValue(self.0.as_ptr(), attr.as_ptr(), &mut value) };
+        // Wrap before checking the error so a value is never leaked
+        let value = unsafe { CFType::wrap(value) };
+        if err == K_AX_ERROR_SUCCESS {
+            value
+        } else {
+            None
+        }
+    }
+
+    fn string_attribute(&self, name: &str) -> Option<String> {
+        string_value(self.attribute(name)?)
+    }
+
+    fn range_attribute(&self, name: &str) -> Option<CFRange> {
+        range_value(&self.attribute(name)?)
+    }
+
+    fn element_attribute(&self, name: &str) -> Option<AXElement> {
+        self.attribute(name).map(AXElement)
+    }
+
+    fn set_attribute(&self, name: &str, value: CFTypeRef) -> Result<(), AXError> {
+        let attr = CFString::new(name);
+        match unsafe { AXUIElementSetAttributeValue(self.0.as_ptr(), attr.as_ptr(), value) } {
+            K_AX_ERROR_SUCCESS => Ok(()),
+            err => Err(err),
+        }
+    }
+
+    fn set_string_attribute(&self, name: &str, text: &str) -> Result<(), AXError> {
+        self.set_attribute(name, CFString::new(text).as_ptr())
+    }
+
+    fn set_range_attribute(&self, name: &str, range: CFRange) -> Result<(), AXError> {
+        let value = unsafe {
+            CFType::wrap(AXValueCreate(
+                K_AX_VALUE_CF_RANGE_TYPE,
+                &range as *const CFRange as *const c_void,
+            ))
+        }
+        .expect("AXValueCreate returned null");
+        self.set_attribute(name, value.as_ptr())
+    }
+
+    fn pid(&self) -> Option<i32> {
+        let mut pid: i32 = 0;
+        let err = unsafe { AXUIElementGetPid(self.0.as_ptr(), &mut pid) };
+        (err == K_AX_ERROR_SUCCESS).then_some(pid)
     }
 }
 
+/// `value` as a Rust string, if it is a CFString
+fn string_value(value: CFType) -> Option<String> {
+    value.into_string()?.to_rust_string()
+}
+
+/// `value` as a range, if it is an AXValue holding a CFRange
+fn range_value(value: &CFType) -> Option<CFRange> {
+    let is_range = value.type_id() == unsafe { AXValueGetTypeID() }
+        && unsafe { AXValueGetType(value.as_ptr()) } == K_AX_VALUE_CF_RANGE_TYPE;
+    if !is_range {
+        return None;
+    }
+    let mut range = CFRange::default();
+    let ok = unsafe {
+        AXValueGetValue(
+            value.as_ptr(),
+            K_AX_VALUE_CF_RANGE_TYPE,
+            &mut range as *mut CFRange as *mut c_void,
+        )
+    };
+    ok.then_some(range)
+}
+
+/// The UI element that has keyboard focus, in whichever app is frontmost
+fn focused_element() -> Result<AXElement, String> {
+    AXElement::system_wide()
+        .element_attribute("AXFocusedUIElement")
+        .ok_or_else(|| "Could not get focused element".into())
+}
+
 /// Check if the app has accessibility permission
 pub fn check_permission() -> bool {
     unsafe {
@@ -61,218 +164,328 @@ pub fn check_permission() -> bool {
 /// Request accessibility permission (shows system prompt dialog)
 pub fn request_permission() {
     unsafe {
-        let key = cf_string("AXTrustedCheckOptionPrompt");
-        let keys = [key as *const c_void];
-        let values = [kCFBooleanTrue as *const c_void];
-        let dict = CFDictionaryCreate(
+        let key = CFString::new("AXTrustedCheckOptionPrompt");
+        let keys = [key.as_ptr()];
+        let values = [kCFBooleanTrue];
+        let dict = CFType::wrap(CFDictionaryCreate(
             std::ptr::null(),
             keys.as_ptr(),
             values.as_ptr(),
             1,
             &kCFTypeDictionaryKeyCallBacks as *const c_void,
             &kCFTypeDictionaryValueCallBacks as *const c_void,
-        );
-        AXIsProcessTrustedWithOptions(dict);
-        CFRelease(dict);
-        CFRelease(key);
+        ));
+        if let Some(dict) = dict {
+            AXIsProcessTrustedWithOptions(dict.as_ptr());
+        }
     }
 }
 
 /// Get the currently focused UI element's selected text via AX API
 pub fn get_selected_text_ax() -> Result<String, String> {
-    unsafe {
-        let system_wide = AXUIElementCreateSystemWide();
-        let focused_attr = cf_string("AXFocusedUIElement");
-        let mut focused_element: CFTypeRef = std::ptr::null();
-
-        let err = AXUIElementCopyAttributeValue(
-            system_wide,
-            focused_attr,
-            &mut focused_element,
-        );
+    focused_element()?
+        .string_attribute("AXSelectedText")
+        .ok_or_else(|| "No text selected or accessibility error".into())
+}
 
-        if err != K_AX_ERROR_SUCCESS || focused_element.is_null() {
-            CFRelease(system_wide);
-            CFRelease(focused_attr);
-            return Err("Could not get focused element".into());
-        }
+/// Text of the focused element and its selection as byte offsets, from
+/// `AXValue` and `AXSelectedTextRange`
+pub fn focused_field_text() -> Option<(String, Range<usize>)> {
+    let element = focused_element().ok()?;
+    let value = element.string_attribute("AXValue")?;
+    let range = element.range_attribute("AXSelectedTextRange")?;
+
+    // CFRange offsets are in UTF-16 code units
+    let start = utf16_to_byte(&value, usize::try_from(range.location).ok()?)?;
+    let end = utf16_to_byte(
+        &value,
+        usize::try_from(range.location + range.length).ok()?,
+    )?;
+    Some((value, start..end))
+}
 
-        let selected_text_attr = cf_string("AXSelectedText");
-        let mut selected_text_value: CFTypeRef = std::ptr::null();
+/// Up to `max_chars` characters on each side of the selection in the
+/// focused element
+pub fn selection_context(max_chars: usize) -> Option<SelectionContext> {
+    let (value, selection) = focused_field_text()?;
+    Some(clip_context(
+        &value[..selection.start],
+        &value[selection.end..],
+        max_chars,
+    ))
+}
 
-        let err = AXUIElementCopyAttributeValue(
-            focused_element as AXUIElementRef,
-            selected_text_attr,
-            &mut selected_text_value,
-        );
+/// Select a byte range of the focused element's value
+pub fn select_range_in_focused_element(range: Range<usize>) -> Result<(), String> {
+    let element = focused_element()?;
+    let value = element
+        .string_attribute("AXValue")
+        .ok_or("Focused element does not expose its value")?;
+    let start = byte_to_utf16(&value, range.start);
+    let end = byte_to_utf16(&value, range.end);
+    let (start, end) = start.zip(end).ok_or("Range is outside the field's value")?;
+    let range = CFRange {
+        location: start as isize,
+        length: end.saturating_sub(start) as isize,
+    };
+    element
+        .set_range_attribute("AXSelectedTextRange", range)
+        .map_err(|err| format!("Failed to set selection (AXError {})", err))
+}
 
-        let result = if err == K_AX_ERROR_SUCCESS && !selected_text_value.is_null() {
-            // Convert CFString to Rust String
-            let cf_str = selected_text_value as *const c_void;
-            let length = CFStringGetLength(cf_str);
-            let max_size = CFStringGetMaximumSizeForEncoding(length, K_CF_STRING_ENCODING_UTF8) + 1;
-            let mut buffer = vec![0u8; max_size as usize];
-            let success = CFStringGetCString(
-                cf_str,
-                buffer.as_mut_ptr() as *mut i8,
-                max_size,
-                K_CF_STRING_ENCODING_UTF8,
-            );
-            if success {
-                let c_str = std::ffi::CStr::from_ptr(buffer.as_ptr() as *const i8);
-                Ok(c_str.to_string_lossy().into_owned())
-            } else {
-                Err("Failed to convert selected text to string".into())
-            }
-        } else {
-            Err("No text selected or accessibility error".into())
-        };
+/// Why reading or typing into the focused element is unsafe, if it is: a
+/// password field by role or subrole (web password inputs only set the
+/// subrole), or an app holding system-wide secure keyboard entry, as
+/// terminals and password managers do
+pub fn secure_input_reason() -> Option<String> {
+    if unsafe { IsSecureEventInputEnabled() } != 0 {
+        return Some("Secure keyboard entry is turned on by another app".into());
+    }
 
-        if !selected_text_value.is_null() {
-            CFRelease(selected_text_value);
-        }
-        CFRelease(selected_text_attr);
-        CFRelease(focused_element);
-        CFRelease(focused_attr);
-        CFRelease(system_wide);
+    let element = focused_element().ok()?;
+    let is_password = ["AXRole", "AXSubrole"].iter().any(|attr| {
+        element
+            .string_attribute(attr)
+            .is_some_and(|value| value.contains("SecureTextField"))
+    });
+    is_password.then(|| "The focused field is a password field".into())
+}
 
-        result
-    }
+/// Get the process id of the application that owns the focused UI element
+pub fn focused_app_pid() -> Option<i32> {
+    focused_element().ok()?.pid()
 }
 
-/// Check if the focused element is a secure text field
-pub fn is_secure_field() -> bool {
-    unsafe {
-        let system_wide = AXUIElementCreateSystemWide();
-        let focused_attr = cf_string("AXFocusedUIElement");
-        let mut focused_element: CFTypeRef = std::ptr::null();
-
-        let err = AXUIElementCopyAttributeValue(
-            system_wide,
-            focused_attr,
-            &mut focused_element,
-        );
+/// Bring the application with the given process id to the front
+pub fn activate_app(pid: i32) -> Result<(), String> {
+    AXElement::application(pid)
+        .set_attribute("AXFrontmost", unsafe { kCFBooleanTrue })
+        .map_err(|err| format!("Failed to activate target app (AXError {})", err))
+}
 
-        if err != K_AX_ERROR_SUCCESS || focused_element.is_null() {
-            CFRelease(system_wide);
-            CFRelease(focused_attr);
-            return false;
-        }
+/// Title of the focused window of the application with the given process id
+pub fn focused_window_title(pid: i32) -> Option<String> {
+    AXElement::application(pid)
+        .element_attribute("AXFocusedWindow")?
+        .string_attribute("AXTitle")
+        .filter(|t| !t.is_empty())
+}
 
-        let role_attr = cf_string("AXRole");
-        let mut role_value: CFTypeRef = std::ptr::null();
+/// Select `text` in the focused element when it ends at the cursor or occurs
+/// exactly once in the field's value
+pub fn select_text_in_focused_element(text: &str) -> Result<(), String> {
+    let element = focused_element()?;
+
+    let located = element
+        .string_attribute("AXValue")
+        .zip(element.range_attribute("AXSelectedTextRange"))
+        .and_then(|(field, cursor)| {
+            // CFRange offsets are in UTF-16 code units
+            let field: Vec<u16> = field.encode_utf16().collect();
+            let needle: Vec<u16> = text.encode_utf16().collect();
+            let cursor_end = (cursor.location + cursor.length).max(0) as usize;
+            locate_text(&field, &needle, cursor_end)
+        })
+        .ok_or("Replaced text not found in focused element")?;
+
+    let range = CFRange {
+        location: located as isize,
+        length: text.encode_utf16().count() as isize,
+    };
+    element
+        .set_range_attribute("AXSelectedTextRange", range)
+        .map_err(|err| format!("Failed to set selection (AXError {})", err))
+}
 
-        let err = AXUIElementCopyAttributeValue(
-            focused_element as AXUIElementRef,
-            role_attr,
-            &mut role_value,
-        );
+/// Roles whose value is plain text, so writing a whole new `AXValue` can't
+/// flatten formatting. Rich text views (Mail, TextEdit, Notes) are text areas.
+const PLAIN_TEXT_ROLES: [&str; 2] = ["AXTextField", "AXComboBox"];
+
+/// Replace the selection in the focused element by setting `AXSelectedText`,
+/// falling back to rewriting `AXValue` around `AXSelectedTextRange` in plain
+/// text fields. The value is read back after each attempt; on Err whatever
+/// took the selection's place is selected, so the caller's paste replaces it.
+pub fn replace_selected_text_ax(text: &str) -> Result<(), String> {
+    let element = focused_element()?;
+
+    // What the field should read afterwards, to verify the write
+    let (before, selection) = element
+        .string_attribute("AXValue")
+        .zip(element.range_attribute("AXSelectedTextRange"))
+        .ok_or("Focused element does not expose its value and selection")?;
+    let expected = splice_utf16(&before, selection, text)
+        .ok_or("Selection range is outside the field's value")?;
+    let reads_expected = || element.string_attribute("AXValue").as_deref() == Some(&*expected);
+
+    let mut written =
+        element.set_string_attribute("AXSelectedText", text).is_ok() && reads_expected();
+
+    let plain_text = element
+        .string_attribute("AXRole")
+        .is_some_and(|role| PLAIN_TEXT_ROLES.contains(&role.as_str()));
+    if !written && plain_text {
+        // Some fields ignore AXSelectedText but accept a whole new AXValue
+        written = element.set_string_attribute("AXValue", &expected).is_ok() && reads_expected();
+        if written {
+            // Leave the cursor after the inserted text, as a paste would
+            let cursor = CFRange {
+                location: selection.location + text.encode_utf16().count() as isize,
+                length: 0,
+            };
+            let _ = element.set_range_attribute("AXSelectedTextRange", cursor);
+        }
+    }
 
-        let is_secure = if err == K_AX_ERROR_SUCCESS && !role_value.is_null() {
-            let cf_str = role_value as *const c_void;
-            let length = CFStringGetLength(cf_str);
-            let max_size = CFStringGetMaximumSizeForEncoding(length, K_CF_STRING_ENCODING_UTF8) + 1;
-            let mut buffer = vec![0u8; max_size as usize];
-            let success = CFStringGetCString(
-                cf_str,
-                buffer.as_mut_ptr() as *mut i8,
-                max_size,
-                K_CF_STRING_ENCODING_UTF8,
-            );
-            if success {
-                let c_str = std::ffi::CStr::from_ptr(buffer.as_ptr() as *const i8);
-                c_str.to_string_lossy().contains("SecureTextField")
-            } else {
-                false
-            }
-        } else {
-            false
-        };
+    if written {
+        return Ok(());
+    }
 
-        if !role_value.is_null() {
-            CFRelease(role_value);
+    // Rewriting the whole value would flatten rich text, so a half-applied
+    // write is selected instead and the paste goes over it
+    let span = match element.string_attribute("AXValue") {
+        Some(current) if current != before => replaced_span(&before, selection, &current),
+        _ => Some(selection),
+    };
+    match span {
+        Some(span) => {
+            let _ = element.set_range_attribute("AXSelectedTextRange", span);
+            Err("Focused element did not accept the replacement".into())
         }
-        CFRelease(role_attr);
-        CFRelease(focused_element);
-        CFRelease(focused_attr);
-        CFRelease(system_wide);
+        None => Err("Focused element changed outside the selection during the replacement".into()),
+    }
+}
 
-        is_secure
+/// The UTF-16 range of `after` that took the place of `selection` in
+/// `before`; `None` when text outside the selection changed too
+fn replaced_span(before: &str, selection: CFRange, after: &str) -> Option<CFRange> {
+    let before: Vec<u16> = before.encode_utf16().collect();
+    let after: Vec<u16> = after.encode_utf16().collect();
+    let start = usize::try_from(selection.location).ok()?;
+    let end = start.checked_add(usize::try_from(selection.length).ok()?)?;
+    let (prefix, suffix) = (before.get(..start)?, before.get(end..)?);
+    if after.len() < prefix.len() + suffix.len()
+        || !after.starts_with(prefix)
+        || !after.ends_with(suffix)
+    {
+        return None;
     }
+    Some(CFRange {
+        location: selection.location,
+        length: (after.len() - prefix.len() - suffix.len()) as isize,
+    })
 }
 
-// Additional CoreFoundation string helpers
-extern "C" {
-    fn CFStringGetLength(the_string: *const c_void) -> isize;
-    fn CFStringGetMaximumSizeForEncoding(length: isize, encoding: u32) -> isize;
-    fn CFStringGetCString(
-        the_string: *const c_void,
-        buffer: *mut i8,
-        buffer_size: isize,
-        encoding: u32,
-    ) -> bool;
+/// `value` with the UTF-16 `range` replaced by `text`; `None` when the range
+/// falls outside `value` or splits a surrogate pair
+fn splice_utf16(value: &str, range: CFRange, text: &str) -> Option<String> {
+    let units: Vec<u16> = value.encode_utf16().collect();
+    let start = usize::try_from(range.location).ok()?;
+    let end = start.checked_add(usize::try_from(range.length).ok()?)?;
+    if end > units.len() {
+        return None;
+    }
+    let mut spliced = units[..start].to_vec();
+    spliced.extend(text.encode_utf16());
+    spliced.extend_from_slice(&units[end..]);
+    String::from_utf16(&spliced).ok()
+}
+
+/// Byte offset in `text` of UTF-16 offset `unit`; `None` past the end or
+/// inside a surrogate pair
+fn utf16_to_byte(text: &str, unit: usize) -> Option<usize> {
+    let mut units = 0;
+    for (byte, c) in text.char_indices() {
+        if units >= unit {
+            return (units == unit).then_some(byte);
+        }
+        units += c.len_utf16();
+    }
+    (units == unit).then_some(text.len())
+}
+
+/// UTF-16 offset of byte offset `byte` in `text`; `None` off a char boundary
+fn byte_to_utf16(text: &str, byte: usize) -> Option<usize> {
+    text.get(..byte).map(|prefix| prefix.encode_utf16().count())
+}
+
+/// Find where `needle` starts in `haystack`, preferring a match that ends at
+/// `cursor`; otherwise only an unambiguous single match is returned.
+fn locate_text(haystack: &[u16], needle: &[u16], cursor: usize) -> Option<usize> {
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return None;
+    }
+    if cursor >= needle.len()
+        && cursor <= haystack.len()
+        && haystack[cursor - needle.len()..cursor] == *needle
+    {
+        return Some(cursor - needle.len());
+    }
+    let mut matches = haystack
+        .windows(needle.len())
+        .enumerate()
+        .filter(|(_, w)| *w == needle)
+        .map(|(i, _)| i);
+    match (matches.next(), matches.next()) {
+        (Some(start), None) => Some(start),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn check_permission_does_not_crash() {
-        // This was the root cause of the onboarding crash:
-        // check_permission() previously created a malformed CFDictionary
-        // that caused a SIGSEGV in AXIsProcessTrustedWithOptions.
-        // Now it passes NULL which safely checks without prompting.
-        let result = check_permission();
-        // Result depends on system state, just verify no crash
-        assert!(result == true || result == false);
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFNumberCreate(allocator: *const c_void, the_type: isize, value: *const c_void)
+            -> CFTypeRef;
     }
 
-    #[test]
-    fn request_permission_does_not_crash() {
-        // Verify CFDictionaryCreate with proper callbacks doesn't crash.
-        // Note: This will show the system accessibility prompt dialog
-        // in CI/interactive environments, but won't crash.
-        request_permission();
-    }
+    const K_CF_NUMBER_SINT32_TYPE: isize = 3;
 
-    #[test]
-    fn cf_string_creates_valid_ref() {
+    fn range(location: isize, length: isize) -> CFType {
+        let range = CFRange { location, length };
         unsafe {
-            let s = cf_string("AXFocusedUIElement");
-            assert!(!s.is_null());
-            CFRelease(s);
+            CFType::wrap(AXValueCreate(
+                K_AX_VALUE_CF_RANGE_TYPE,
+                &range as *const CFRange as *const c_void,
+            ))
         }
+        .unwrap()
+    }
+
+    fn number(value: i32) -> CFType {
+        let ptr = &value as *const i32 as *const c_void;
+        unsafe { CFType::wrap(CFNumberCreate(std::ptr::null(), K_CF_NUMBER_SINT32_TYPE, ptr)) }
+            .unwrap()
     }
 
     #[test]
-    fn cf_string_roundtrip() {
-        unsafe {
-            let original = "TestString123";
-            let cf = cf_string(original);
-            assert!(!cf.is_null());
-
-            let length = CFStringGetLength(cf);
-            assert!(length > 0);
-
-            let max_size = CFStringGetMaximumSizeForEncoding(length, K_CF_STRING_ENCODING_UTF8) + 1;
-            let mut buffer = vec![0u8; max_size as usize];
-            let success = CFStringGetCString(
-                cf,
-                buffer.as_mut_ptr() as *mut i8,
-                max_size,
-                K_CF_STRING_ENCODING_UTF8,
-            );
-            assert!(success);
-
-            let c_str = std::ffi::CStr::from_ptr(buffer.as_ptr() as *const i8);
-            assert_eq!(c_str.to_string_lossy(), original);
-
-            CFRelease(cf);
+    fn typed_values_check_the_value_type() {
+        let text = CFString::new("AXApplication").as_cf_type().clone();
+        assert_eq!(string_value(text.clone()).as_deref(), Some("AXApplication"));
+        assert_eq!(range_value(&text), None);
+
+        assert_eq!(range_value(&range(3, 4)), Some(CFRange { location: 3, length: 4 }));
+        assert_eq!(string_value(range(3, 4)), None);
+
+        assert_eq!(string_value(number(7)), None);
+        assert_eq!(range_value(&number(7)), None);
+    }
+
+    #[test]
+    fn typed_values_release_what_they_take() {
+        let value = range(0, 1);
+        let base = value.retain_count();
+        for _ in 0..100 {
+            let _ = range_value(&value);
+            let _ = string_value(value.clone());
         }
+        assert_eq!(value.retain_count(), base);
     }
 
     #[test]
+    #[ignore = "reads the focused element of whichever app is frontmost"]
     fn get_selected_text_returns_error_without_focused_app() {
         // Without a focused UI element, should return Err, not crash
         let result = get_selected_text_ax();
@@ -280,9 +493,71 @@ mod tests {
     }
 
     #[test]
-    fn is_secure_field_returns_false_without_focused_app() {
-        // Without a focused UI element, should return false, not crash
-        let result = is_secure_field();
-        assert!(!result);
+    fn locate_text_prefers_match_ending_at_cursor() {
+        let utf16 = |s: &str| s.encode_utf16().collect::<Vec<_>>();
+        let field = utf16("fixed text and fixed text");
+        let needle = utf16("fixed text");
+        assert_eq!(locate_text(&field, &needle, 10), Some(0));
+        assert_eq!(locate_text(&field, &needle, 25), Some(15));
+        // Ambiguous when the cursor moved away
+        assert_eq!(locate_text(&field, &needle, 12), None);
+        assert_eq!(locate_text(&utf16("only once here"), &utf16("once"), 0), Some(5));
+        assert_eq!(locate_text(&field, &[], 0), None);
+    }
+
+    #[test]
+    fn splice_utf16_replaces_range_in_code_units() {
+        let range = |location, length| CFRange { location, length };
+        assert_eq!(
+            splice_utf16("teh fox", range(0, 3), "the").as_deref(),
+            Some("the fox")
+        );
+        // The emoji is two UTF-16 units, so "fox" starts at 5
+        assert_eq!(
+            splice_utf16("🦊 a fox", range(5, 3), "dog").as_deref(),
+            Some("🦊 a dog")
+        );
+        assert_eq!(splice_utf16("abc", range(3, 0), "d").as_deref(), Some("abcd"));
+        assert_eq!(splice_utf16("abc", range(2, 5), "d"), None);
+        // Splitting the emoji's surrogate pair is rejected
+        assert_eq!(splice_utf16("🦊", range(1, 1), "x"), None);
+    }
+
+    #[test]
+    fn replaced_span_covers_what_took_the_selections_place() {
+        let range = |location, length| CFRange { location, length };
+        let span = replaced_span("say teh word", range(4, 3), "say the the word");
+        assert_eq!(span, Some(range(4, 7)));
+        assert_eq!(replaced_span("🦊 teh", range(3, 3), "🦊 "), Some(range(3, 0)));
+        // Text outside the selection changed as well
+        assert_eq!(replaced_span("say teh word", range(4, 3), "Say the word"), None);
+        assert_eq!(replaced_span("abc", range(2, 5), "abc"), None);
+    }
+
+    #[test]
+    fn utf16_offsets_convert_to_bytes_and_back() {
+        let text = "a🦊é b";
+        // 🦊 is 4 bytes and 2 UTF-16 units, é is 2 bytes and 1 unit
+        assert_eq!(utf16_to_byte(text, 0), Some(0));
+        assert_eq!(utf16_to_byte(text, 3), Some(5));
+        assert_eq!(utf16_to_byte(text, 2), None);
+        assert_eq!(utf16_to_byte(text, 6), Some(text.len()));
+        assert_eq!(utf16_to_byte(text, 7), None);
+        assert_eq!(byte_to_utf16(text, 5), Some(3));
+        assert_eq!(byte_to_utf16(text, 2), None);
+        assert_eq!(byte_to_utf16(text, text.len()), Some(6));
+    }
+
+    #[test]
+    #[ignore = "queries the live accessibility tree"]
+    fn focused_window_title_is_none_for_unknown_pid() {
+        assert_eq!(focused_window_title(-1), None);
+    }
+
+    #[test]
+    #[ignore = "depends on the focused app and the system's secure input state"]
+    fn secure_input_reason_does_not_crash() {
+        // Depends on the focused app and secure input state; must not crash
+        let _ = secure_input_reason();
     }
 }
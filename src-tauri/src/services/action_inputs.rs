@@ -0,0 +1,134 @@
+use crate::models::{ActionInput, ActionInputKind};
+use crate::services::template::{self, BUILTIN_VARIABLES};
+use std::collections::{HashMap, HashSet};
+
+/// Check an action's inputs can be used as template variables and that
+/// choices have something to pick
+pub fn validate(inputs: &[ActionInput]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    for input in inputs {
+        let name = input.name.as_str();
+        if !template::is_valid_name(name) {
+            return Err(format!("Input '{}' is not a valid variable name", name));
+        }
+        if BUILTIN_VARIABLES.contains(&name) {
+            return Err(format!("Input '{}' clashes with the built-in {{{{{}}}}}", name, name));
+        }
+        if !seen.insert(name) {
+            return Err(format!("Input '{}' is defined twice", name));
+        }
+        if input.kind == ActionInputKind::Choice {
+            if input.options.is_empty() {
+                return Err(format!("Input '{}' needs at least one choice", name));
+            }
+            if !input.default.is_empty() && !input.options.contains(&input.default) {
+                return Err(format!("Default for '{}' is not one of its choices", name));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Template values for `inputs` from what the user `entered`, falling back to
+/// each input's default
+pub fn resolve_values(
+    inputs: &[ActionInput],
+    entered: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, String> {
+    inputs
+        .iter()
+        .map(|input| {
+            let value = entered
+                .get(&input.name)
+                .cloned()
+                .unwrap_or_else(|| input.default.clone());
+            let value = match input.kind {
+                ActionInputKind::Text => value,
+                ActionInputKind::Choice if value.is_empty() => {
+                    input.options.first().cloned().unwrap_or_default()
+                }
+                ActionInputKind::Choice if input.options.contains(&value) => value,
+                ActionInputKind::Choice => {
+                    return Err(format!("'{}' is not a choice for {}", value, label(input)));
+                }
+            };
+            Ok((input.name.clone(), value))
+        })
+        .collect()
+}
+
+/// What the quick-input panel calls the input
+pub fn label(input: &ActionInput) -> &str {
+    if input.label.trim().is_empty() {
+        &input.name
+    } else {
+        &input.label
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(name: &str, default: &str) -> ActionInput {
+        ActionInput {
+            name: name.into(),
+            default: default.into(),
+            ..Default::default()
+        }
+    }
+
+    fn choice(name: &str, options: &[&str], default: &str) -> ActionInput {
+        ActionInput {
+            name: name.into(),
+            label: "Tone".into(),
+            kind: ActionInputKind::Choice,
+            options: options.iter().map(|o| o.to_string()).collect(),
+            default: default.into(),
+        }
+    }
+
+    #[test]
+    fn validate_rejects_bad_names_and_choices() {
+        assert!(validate(&[text("language", "")]).is_err());
+        assert!(validate(&[text("target language", "")]).is_err());
+        assert_eq!(
+            validate(&[text("tone", ""), text("tone", "")]).unwrap_err(),
+            "Input 'tone' is defined twice"
+        );
+        assert_eq!(
+            validate(&[choice("tone", &[], "")]).unwrap_err(),
+            "Input 'tone' needs at least one choice"
+        );
+        assert_eq!(
+            validate(&[choice("tone", &["formal"], "casual")]).unwrap_err(),
+            "Default for 'tone' is not one of its choices"
+        );
+        assert!(validate(&[text("target", "German"), choice("tone", &["formal", "casual"], "")]).is_ok());
+    }
+
+    #[test]
+    fn resolve_values_falls_back_to_defaults() {
+        let inputs = [text("target", "German"), choice("tone", &["formal", "casual"], "")];
+        let values = resolve_values(&inputs, &HashMap::new()).unwrap();
+        assert_eq!(values["target"], "German");
+        assert_eq!(values["tone"], "formal");
+
+        let entered = HashMap::from([
+            ("target".to_string(), "French".to_string()),
+            ("tone".to_string(), "casual".to_string()),
+        ]);
+        let values = resolve_values(&inputs, &entered).unwrap();
+        assert_eq!(values["target"], "French");
+        assert_eq!(values["tone"], "casual");
+    }
+
+    #[test]
+    fn resolve_values_rejects_unknown_choices() {
+        let entered = HashMap::from([("tone".to_string(), "angry".to_string())]);
+        assert_eq!(
+            resolve_values(&[choice("tone", &["formal"], "")], &entered).unwrap_err(),
+            "'angry' is not a choice for Tone"
+        );
+    }
+}
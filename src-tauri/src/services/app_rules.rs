@@ -0,0 +1,173 @@
+use crate::models::{AppContext, RewriteAction, TextInteractionError};
+
+/// Password managers and keychains, used until the user edits the list
+pub const DEFAULT_SECURE_APPS: &[&str] = &[
+    "com.1password.1password",
+    "com.agilebits.onepassword7",
+    "com.bitwarden.desktop",
+    "com.apple.keychainaccess",
+    "com.apple.Passwords",
+    "KeePassXC",
+];
+
+/// Whether `pattern` names `app`, by display name or bundle id
+fn matches(pattern: &str, app: &AppContext) -> bool {
+    let pattern = pattern.trim();
+    !pattern.is_empty()
+        && (app.name.eq_ignore_ascii_case(pattern)
+            || app
+                .bundle_id
+                .as_deref()
+                .is_some_and(|id| id.eq_ignore_ascii_case(pattern)))
+}
+
+/// Check the action's include and exclude lists against the app the
+/// selection is in. An include list can't be satisfied when the app is unknown.
+pub fn check_allowed(action: &RewriteAction, app: Option<&AppContext>) -> Result<(), String> {
+    if let Some(app) = app {
+        if action.exclude_apps.iter().any(|p| matches(p, app)) {
+            return Err(format!("'{}' is disabled in {}", action.name, app.name));
+        }
+    }
+
+    let include: Vec<&str> = action
+        .include_apps
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .collect();
+    if include.is_empty() || app.is_some_and(|app| include.iter().any(|p| matches(p, app))) {
+        Ok(())
+    } else {
+        Err(format!("'{}' only runs in {}", action.name, include.join(", ")))
+    }
+}
+
+/// Refuse to touch the selection in apps on the user's secure-app denylist
+pub fn check_secure_app(secure_apps: &[String], app: Option<&AppContext>) -> Result<(), String> {
+    match app {
+        Some(app) if secure_apps.iter().any(|p| matches(p, app)) => {
+            let reason = format!("{} is on the secure apps list", app.name);
+            Err(TextInteractionError::SecureField(reason).to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// `action` with the first prompt override for `app` applied
+pub fn resolve(action: &RewriteAction, app: Option<&AppContext>) -> RewriteAction {
+    let mut resolved = action.clone();
+    let Some(app) = app else {
+        return resolved;
+    };
+    if let Some(o) = action.app_overrides.iter().find(|o| matches(&o.app, app)) {
+        if let Some(system_prompt) = &o.system_prompt {
+            resolved.system_prompt = system_prompt.clone();
+        }
+        if let Some(user_template) = &o.user_template {
+            resolved.user_template = user_template.clone();
+        }
+        if let Some(output_rules) = &o.output_rules {
+            resolved.output_rules = output_rules.clone();
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AppPromptOverride, FallbackScope, ReplaceMode};
+
+    fn action() -> RewriteAction {
+        RewriteAction {
+            id: "tone".into(),
+            name: "Fix Tone".into(),
+            hotkey: String::new(),
+            system_prompt: "Neutral tone.".into(),
+            user_template: "{{text}}".into(),
+            output_rules: "Only the text.".into(),
+            enabled: true,
+            replace_mode: ReplaceMode::Immediate,
+            preserve_formatting: false,
+            include_apps: Vec::new(),
+            exclude_apps: Vec::new(),
+            app_overrides: vec![
+                AppPromptOverride {
+                    app: "com.tinyspeck.slackmacgap".into(),
+                    system_prompt: Some("Casual tone.".into()),
+                    ..Default::default()
+                },
+                AppPromptOverride {
+                    app: "mail".into(),
+                    system_prompt: Some("Formal tone.".into()),
+                    output_rules: Some("Keep the greeting.".into()),
+                    ..Default::default()
+                },
+            ],
+            context_chars: None,
+            fallback_scope: FallbackScope::None,
+            inputs: Vec::new(),
+            steps: Vec::new(),
+            candidates: None,
+            examples: Vec::new(),
+        }
+    }
+
+    fn app(name: &str, bundle_id: &str) -> AppContext {
+        AppContext {
+            name: name.into(),
+            bundle_id: Some(bundle_id.into()),
+            window_title: None,
+        }
+    }
+
+    #[test]
+    fn overrides_match_bundle_id_or_name() {
+        let slack = resolve(&action(), Some(&app("Slack", "com.tinyspeck.slackmacgap")));
+        assert_eq!(slack.system_prompt, "Casual tone.");
+        assert_eq!(slack.output_rules, "Only the text.");
+
+        let mail = resolve(&action(), Some(&app("Mail", "com.apple.mail")));
+        assert_eq!(mail.system_prompt, "Formal tone.");
+        assert_eq!(mail.output_rules, "Keep the greeting.");
+
+        let other = resolve(&action(), Some(&app("Notes", "com.apple.Notes")));
+        assert_eq!(other.system_prompt, "Neutral tone.");
+        assert_eq!(resolve(&action(), None).system_prompt, "Neutral tone.");
+    }
+
+    #[test]
+    fn exclude_list_blocks_matching_app() {
+        let action = RewriteAction {
+            exclude_apps: vec!["Terminal".into()],
+            ..action()
+        };
+        assert!(check_allowed(&action, Some(&app("Terminal", "com.apple.Terminal"))).is_err());
+        assert!(check_allowed(&action, Some(&app("Mail", "com.apple.mail"))).is_ok());
+        assert!(check_allowed(&action, None).is_ok());
+    }
+
+    #[test]
+    fn include_list_requires_a_known_matching_app() {
+        let action = RewriteAction {
+            include_apps: vec!["com.apple.mail".into(), " ".into()],
+            ..action()
+        };
+        assert!(check_allowed(&action, Some(&app("Mail", "com.apple.mail"))).is_ok());
+        let err = check_allowed(&action, Some(&app("Slack", "com.tinyspeck.slackmacgap")))
+            .unwrap_err();
+        assert_eq!(err, "'Fix Tone' only runs in com.apple.mail");
+        assert!(check_allowed(&action, None).is_err());
+    }
+
+    #[test]
+    fn secure_app_denylist_explains_the_block() {
+        let secure_apps = vec!["com.1password.1password".to_string()];
+        let err = check_secure_app(&secure_apps, Some(&app("1Password", "com.1password.1password")))
+            .unwrap_err();
+        assert_eq!(err, "Cannot read secure input: 1Password is on the secure apps list");
+        assert!(check_secure_app(&secure_apps, Some(&app("Mail", "com.apple.mail"))).is_ok());
+        assert!(check_secure_app(&secure_apps, None).is_ok());
+    }
+}
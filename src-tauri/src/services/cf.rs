@@ -0,0 +1,207 @@
+// Owned CoreFoundation references. Every Create/Copy result is wrapped right
+// away and released on drop, so early returns can't leak.
+use std::ffi::c_void;
+
+pub type CFTypeRef = *const c_void;
+pub type CFTypeID = usize;
+type CFIndex = isize;
+
+const K_CF_STRING_ENCODING_UTF8: u32 = 0x08000100;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CFRange {
+    pub location: CFIndex,
+    pub length: CFIndex,
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFRetain(cf: CFTypeRef) -> CFTypeRef;
+    fn CFRelease(cf: CFTypeRef);
+    fn CFGetRetainCount(cf: CFTypeRef) -> CFIndex;
+    fn CFGetTypeID(cf: CFTypeRef) -> CFTypeID;
+    fn CFStringGetTypeID() -> CFTypeID;
+    fn CFStringCreateWithBytes(
+        alloc: *const c_void,
+        bytes: *const u8,
+        num_bytes: CFIndex,
+        encoding: u32,
+        is_external_representation: bool,
+    ) -> CFTypeRef;
+    fn CFStringGetLength(the_string: CFTypeRef) -> CFIndex;
+    fn CFStringGetBytes(
+        the_string: CFTypeRef,
+        range: CFRange,
+        encoding: u32,
+        loss_byte: u8,
+        is_external_representation: bool,
+        buffer: *mut u8,
+        max_buf_len: CFIndex,
+        used_buf_len: *mut CFIndex,
+    ) -> CFIndex;
+}
+
+/// A CoreFoundation object we hold one retain on
+#[derive(Debug)]
+pub struct CFType(CFTypeRef);
+
+impl CFType {
+    /// Take over a reference returned by a Create or Copy function (the
+    /// "create rule"); `None` for null
+    ///
+    /// # Safety
+    /// `ptr` must be null or a CF object whose +1 reference the caller gives up
+    pub unsafe fn wrap(ptr: CFTypeRef) -> Option<Self> {
+        (!ptr.is_null()).then_some(Self(ptr))
+    }
+
+    /// Retain a reference we don't own (the "get rule"), e.g. a constant
+    ///
+    /// # Safety
+    /// `ptr` must be null or a valid CF object
+    pub unsafe fn retain(ptr: CFTypeRef) -> Option<Self> {
+        (!ptr.is_null()).then(|| Self(CFRetain(ptr)))
+    }
+
+    pub fn as_ptr(&self) -> CFTypeRef {
+        self.0
+    }
+
+    pub fn retain_count(&self) -> isize {
+        unsafe { CFGetRetainCount(self.0) }
+    }
+
+    pub fn type_id(&self) -> CFTypeID {
+        unsafe { CFGetTypeID(self.0) }
+    }
+
+    /// The same object as a string, if it is one
+    pub fn into_string(self) -> Option<CFString> {
+        let is_string = self.type_id() == unsafe { CFStringGetTypeID() };
+        is_string.then_some(CFString(self))
+    }
+}
+
+impl Clone for CFType {
+    fn clone(&self) -> Self {
+        Self(unsafe { CFRetain(self.0) })
+    }
+}
+
+impl Drop for CFType {
+    fn drop(&mut self) {
+        unsafe { CFRelease(self.0) }
+    }
+}
+
+/// An owned CFString
+#[derive(Debug, Clone)]
+pub struct CFString(CFType);
+
+impl CFString {
+    /// Interior NULs are kept, unlike with `CFStringCreateWithCString`
+    pub fn new(s: &str) -> Self {
+        let ptr = unsafe {
+            CFStringCreateWithBytes(
+                std::ptr::null(),
+                s.as_ptr(),
+                s.len() as CFIndex,
+                K_CF_STRING_ENCODING_UTF8,
+                false,
+            )
+        };
+        Self(unsafe { CFType::wrap(ptr) }.expect("CFStringCreateWithBytes returned null"))
+    }
+
+    pub fn as_ptr(&self) -> CFTypeRef {
+        self.0.as_ptr()
+    }
+
+    pub fn as_cf_type(&self) -> &CFType {
+        &self.0
+    }
+
+    /// Copy the contents into a Rust string
+    pub fn to_rust_string(&self) -> Option<String> {
+        unsafe {
+            let range = CFRange {
+                location: 0,
+                length: CFStringGetLength(self.as_ptr()),
+            };
+            // First ask for the UTF-8 length, then convert
+            let mut len: CFIndex = 0;
+            let converted = CFStringGetBytes(
+                self.as_ptr(),
+                range,
+                K_CF_STRING_ENCODING_UTF8,
+                0,
+                false,
+                std::ptr::null_mut(),
+                0,
+                &mut len,
+            );
+            if converted != range.length {
+                return None;
+            }
+            let mut buffer = vec![0u8; len as usize];
+            CFStringGetBytes(
+                self.as_ptr(),
+                range,
+                K_CF_STRING_ENCODING_UTF8,
+                0,
+                false,
+                buffer.as_mut_ptr(),
+                len,
+                &mut len,
+            );
+            buffer.truncate(len as usize);
+            String::from_utf8(buffer).ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_round_trips_unicode_and_nul() {
+        for text in ["AXFocusedUIElement", "", "🦊 naïve\0tail"] {
+            assert_eq!(CFString::new(text).to_rust_string().as_deref(), Some(text));
+        }
+    }
+
+    /// Long and non-ASCII so it isn't stored as a tagged pointer, which has
+    /// no real retain count
+    const HEAP_STRING: &str = "a string that has to live on the heap — 🦊";
+
+    #[test]
+    fn clone_and_drop_balance_retains() {
+        let s = CFString::new(HEAP_STRING);
+        let base = s.as_cf_type().retain_count();
+        let copy = s.clone();
+        assert_eq!(s.as_cf_type().retain_count(), base + 1);
+        drop(copy);
+        assert_eq!(s.as_cf_type().retain_count(), base);
+    }
+
+    #[test]
+    fn retain_takes_its_own_reference() {
+        let s = CFString::new(HEAP_STRING);
+        let base = s.as_cf_type().retain_count();
+        let retained = unsafe { CFType::retain(s.as_ptr()) }.unwrap();
+        assert_eq!(s.as_cf_type().retain_count(), base + 1);
+        // Downcasting moves the reference without another retain
+        let back = retained.into_string().unwrap();
+        assert_eq!(s.as_cf_type().retain_count(), base + 1);
+        drop(back);
+        assert_eq!(s.as_cf_type().retain_count(), base);
+    }
+
+    #[test]
+    fn null_is_not_wrapped() {
+        assert!(unsafe { CFType::wrap(std::ptr::null()) }.is_none());
+        assert!(unsafe { CFType::retain(std::ptr::null()) }.is_none());
+    }
+}
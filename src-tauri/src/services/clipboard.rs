@@ -1,13 +1,19 @@
 use rdev::{simulate, EventType, Key};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-/// Simulate Cmd+C to copy selected text to clipboard
+/// Shortcut modifier: Cmd on macOS, Ctrl elsewhere
+#[cfg(target_os = "macos")]
+const MODIFIER: Key = Key::MetaLeft;
+#[cfg(not(target_os = "macos"))]
+const MODIFIER: Key = Key::ControlLeft;
+
+/// Simulate Cmd/Ctrl+C to copy selected text to clipboard
 pub fn simulate_copy() -> Result<(), String> {
     let delay = Duration::from_millis(20);
 
-    simulate(&EventType::KeyPress(Key::MetaLeft))
-        .map_err(|e| format!("Failed to press Meta: {:?}", e))?;
+    simulate(&EventType::KeyPress(MODIFIER))
+        .map_err(|e| format!("Failed to press modifier: {:?}", e))?;
     thread::sleep(delay);
     simulate(&EventType::KeyPress(Key::KeyC))
         .map_err(|e| format!("Failed to press C: {:?}", e))?;
@@ -15,20 +21,33 @@ pub fn simulate_copy() -> Result<(), String> {
     simulate(&EventType::KeyRelease(Key::KeyC))
         .map_err(|e| format!("Failed to release C: {:?}", e))?;
     thread::sleep(delay);
-    simulate(&EventType::KeyRelease(Key::MetaLeft))
-        .map_err(|e| format!("Failed to release Meta: {:?}", e))?;
+    simulate(&EventType::KeyRelease(MODIFIER))
+        .map_err(|e| format!("Failed to release modifier: {:?}", e))?;
 
-    // Wait for clipboard to be populated
-    thread::sleep(Duration::from_millis(100));
+    // Callers poll for the clipboard change rather than sleeping a fixed time
     Ok(())
 }
 
-/// Simulate Cmd+V to paste from clipboard
+/// Call `changed` every `interval` until it returns true or `timeout` elapses
+pub fn poll_until(timeout: Duration, interval: Duration, mut changed: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if changed() {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Simulate Cmd/Ctrl+V to paste from clipboard
 pub fn simulate_paste() -> Result<(), String> {
     let delay = Duration::from_millis(20);
 
-    simulate(&EventType::KeyPress(Key::MetaLeft))
-        .map_err(|e| format!("Failed to press Meta: {:?}", e))?;
+    simulate(&EventType::KeyPress(MODIFIER))
+        .map_err(|e| format!("Failed to press modifier: {:?}", e))?;
     thread::sleep(delay);
     simulate(&EventType::KeyPress(Key::KeyV))
         .map_err(|e| format!("Failed to press V: {:?}", e))?;
@@ -36,21 +55,20 @@ pub fn simulate_paste() -> Result<(), String> {
     simulate(&EventType::KeyRelease(Key::KeyV))
         .map_err(|e| format!("Failed to release V: {:?}", e))?;
     thread::sleep(delay);
-    simulate(&EventType::KeyRelease(Key::MetaLeft))
-        .map_err(|e| format!("Failed to release Meta: {:?}", e))?;
+    simulate(&EventType::KeyRelease(MODIFIER))
+        .map_err(|e| format!("Failed to release modifier: {:?}", e))?;
 
     // Wait for paste to complete
     thread::sleep(Duration::from_millis(100));
     Ok(())
 }
 
-/// Simulate Cmd+A to select all (useful for replacing in single-line fields)
-#[allow(dead_code)]
+/// Simulate Cmd/Ctrl+A to select all (useful for replacing in single-line fields)
 pub fn simulate_select_all() -> Result<(), String> {
     let delay = Duration::from_millis(20);
 
-    simulate(&EventType::KeyPress(Key::MetaLeft))
-        .map_err(|e| format!("Failed to press Meta: {:?}", e))?;
+    simulate(&EventType::KeyPress(MODIFIER))
+        .map_err(|e| format!("Failed to press modifier: {:?}", e))?;
     thread::sleep(delay);
     simulate(&EventType::KeyPress(Key::KeyA))
         .map_err(|e| format!("Failed to press A: {:?}", e))?;
@@ -58,9 +76,32 @@ pub fn simulate_select_all() -> Result<(), String> {
     simulate(&EventType::KeyRelease(Key::KeyA))
         .map_err(|e| format!("Failed to release A: {:?}", e))?;
     thread::sleep(delay);
-    simulate(&EventType::KeyRelease(Key::MetaLeft))
-        .map_err(|e| format!("Failed to release Meta: {:?}", e))?;
+    simulate(&EventType::KeyRelease(MODIFIER))
+        .map_err(|e| format!("Failed to release modifier: {:?}", e))?;
 
     thread::sleep(Duration::from_millis(50));
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poll_until_sees_late_change() {
+        let mut calls = 0;
+        let changed = poll_until(Duration::from_millis(200), Duration::from_millis(1), || {
+            calls += 1;
+            calls == 3
+        });
+        assert!(changed);
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn poll_until_times_out() {
+        let start = Instant::now();
+        assert!(!poll_until(Duration::from_millis(20), Duration::from_millis(5), || false));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,252 @@
+use crate::models::{HistoryEntry, HistoryExportFormat};
+use std::io::Write;
+
+/// CSV columns; steps and candidates are written as JSON
+pub const CSV_HEADER: [&str; 15] = [
+    "id",
+    "timestamp",
+    "action_name",
+    "app_name",
+    "original_text",
+    "result_text",
+    "provider",
+    "model",
+    "duration_ms",
+    "tokens_used",
+    "changes_count",
+    "app_bundle_id",
+    "window_title",
+    "steps",
+    "candidates",
+];
+
+/// Write history entries to `out` in the given format
+pub fn write_entries<W: Write>(
+    out: &mut W,
+    entries: &[HistoryEntry],
+    format: HistoryExportFormat,
+) -> std::io::Result<()> {
+    match format {
+        HistoryExportFormat::Jsonl => write_jsonl(out, entries),
+        HistoryExportFormat::Csv => write_csv(out, entries),
+        HistoryExportFormat::Markdown => write_markdown(out, entries),
+    }
+}
+
+fn write_jsonl<W: Write>(out: &mut W, entries: &[HistoryEntry]) -> std::io::Result<()> {
+    for entry in entries {
+        serde_json::to_writer(&mut *out, entry)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn write_csv<W: Write>(out: &mut W, entries: &[HistoryEntry]) -> std::io::Result<()> {
+    write_csv_record(out, &CSV_HEADER.map(String::from))?;
+    for entry in entries {
+        let steps = if entry.steps.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&entry.steps)?
+        };
+        let candidates = if entry.candidates.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&entry.candidates)?
+        };
+        write_csv_record(
+            out,
+            &[
+                entry.id.clone(),
+                entry.timestamp.clone(),
+                entry.action_name.clone(),
+                entry.app_name.clone(),
+                entry.original_text.clone(),
+                entry.result_text.clone(),
+                entry.provider.clone(),
+                entry.model.clone(),
+                entry.duration_ms.to_string(),
+                entry.tokens_used.map(|t| t.to_string()).unwrap_or_default(),
+                entry.changes_count.map(|c| c.to_string()).unwrap_or_default(),
+                entry.app_bundle_id.clone().unwrap_or_default(),
+                entry.window_title.clone().unwrap_or_default(),
+                steps,
+                candidates,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn write_csv_record<W: Write>(out: &mut W, fields: &[String]) -> std::io::Result<()> {
+    let line = fields
+        .iter()
+        .map(|f| csv_field(f))
+        .collect::<Vec<_>>()
+        .join(",");
+    // RFC 4180 records end with CRLF
+    write!(out, "{}\r\n", line)
+}
+
+/// Quote a field per RFC 4180 when it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_markdown<W: Write>(out: &mut W, entries: &[HistoryEntry]) -> std::io::Result<()> {
+    writeln!(out, "# Omni Text History")?;
+    for entry in entries {
+        writeln!(out)?;
+        writeln!(out, "## {} — {}", entry.action_name, entry.timestamp)?;
+        writeln!(out)?;
+        let mut meta = format!("{} / {} · {} ms", entry.provider, entry.model, entry.duration_ms);
+        if !entry.app_name.is_empty() {
+            meta.push_str(&format!(" · {}", entry.app_name));
+        }
+        writeln!(out, "*{}*", meta)?;
+        writeln!(out)?;
+        writeln!(out, "**Before**")?;
+        writeln!(out)?;
+        write_blockquote(out, &entry.original_text)?;
+        // Intermediate outputs of multi-step actions; the last one is "After"
+        if let Some((_, intermediate)) = entry.steps.split_last() {
+            for (i, step) in intermediate.iter().enumerate() {
+                writeln!(out)?;
+                writeln!(
+                    out,
+                    "**Step {}: {}** *({} / {})*",
+                    i + 1,
+                    step.name,
+                    step.provider,
+                    step.model
+                )?;
+                writeln!(out)?;
+                write_blockquote(out, &step.text)?;
+            }
+        }
+        writeln!(out)?;
+        writeln!(out, "**After**")?;
+        writeln!(out)?;
+        write_blockquote(out, &entry.result_text)?;
+        // Alternatives that were offered but not picked
+        for (i, candidate) in entry
+            .candidates
+            .iter()
+            .filter(|c| **c != entry.result_text)
+            .enumerate()
+        {
+            writeln!(out)?;
+            writeln!(out, "**Candidate {}**", i + 1)?;
+            writeln!(out)?;
+            write_blockquote(out, candidate)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_blockquote<W: Write>(out: &mut W, text: &str) -> std::io::Result<()> {
+    for line in text.lines() {
+        if line.is_empty() {
+            writeln!(out, ">")?;
+        } else {
+            writeln!(out, "> {}", line)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::StepOutput;
+
+    fn sample() -> HistoryEntry {
+        HistoryEntry {
+            id: "abc".into(),
+            timestamp: "2026-01-01T10:00:00.000Z".into(),
+            action_name: "Proofread".into(),
+            original_text: "Line one, with comma\nLine \"two\"".into(),
+            result_text: "Fixed".into(),
+            provider: "OpenAI".into(),
+            model: "gpt-4o-mini".into(),
+            duration_ms: 120,
+            ..Default::default()
+        }
+    }
+
+    fn render(format: HistoryExportFormat) -> String {
+        let mut buf = Vec::new();
+        write_entries(&mut buf, &[sample()], format).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn csv_field_quotes_only_when_needed() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("multi\nline"), "\"multi\nline\"");
+    }
+
+    #[test]
+    fn csv_uses_crlf_and_quotes_multiline_text() {
+        let csv = render(HistoryExportFormat::Csv);
+        let expected = "id,timestamp,action_name,app_name,original_text,result_text,provider,model,duration_ms,tokens_used,\
+             changes_count,app_bundle_id,window_title,steps,candidates\r\n\
+             abc,2026-01-01T10:00:00.000Z,Proofread,,\"Line one, with comma\nLine \"\"two\"\"\",Fixed,OpenAI,gpt-4o-mini,120,,,,,,\r\n";
+        assert_eq!(csv, expected);
+    }
+
+    #[test]
+    fn jsonl_roundtrips() {
+        let jsonl = render(HistoryExportFormat::Jsonl);
+        assert_eq!(jsonl.lines().count(), 1);
+        let parsed: HistoryEntry = serde_json::from_str(jsonl.trim_end()).unwrap();
+        assert_eq!(parsed.original_text, sample().original_text);
+    }
+
+    #[test]
+    fn markdown_quotes_before_and_after() {
+        let md = render(HistoryExportFormat::Markdown);
+        assert!(md.contains("## Proofread — 2026-01-01T10:00:00.000Z"));
+        assert!(md.contains("> Line one, with comma\n> Line \"two\"\n"));
+        assert!(md.contains("**After**\n\n> Fixed\n"));
+        assert!(!md.contains("**Step"));
+    }
+
+    #[test]
+    fn markdown_lists_intermediate_steps() {
+        let step = |name: &str, text: &str| StepOutput {
+            name: name.into(),
+            provider: "OpenAI".into(),
+            model: "gpt-4o-mini".into(),
+            text: text.into(),
+            ..Default::default()
+        };
+        let entry = HistoryEntry {
+            steps: vec![step("Proofread", "Fixed long"), step("Shorten", "Fixed")],
+            ..sample()
+        };
+        let mut buf = Vec::new();
+        write_entries(&mut buf, &[entry], HistoryExportFormat::Markdown).unwrap();
+        let md = String::from_utf8(buf).unwrap();
+        assert!(md.contains("**Step 1: Proofread** *(OpenAI / gpt-4o-mini)*\n\n> Fixed long\n"));
+        assert!(!md.contains("Step 2"));
+    }
+
+    #[test]
+    fn markdown_lists_candidates_that_were_not_picked() {
+        let entry = HistoryEntry {
+            candidates: vec!["Fixed".into(), "Repaired".into()],
+            ..sample()
+        };
+        let mut buf = Vec::new();
+        write_entries(&mut buf, &[entry], HistoryExportFormat::Markdown).unwrap();
+        let md = String::from_utf8(buf).unwrap();
+        assert!(md.ends_with("**After**\n\n> Fixed\n\n**Candidate 1**\n\n> Repaired\n"));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::models::{HistoryEntry, HistoryExportFormat};
+use crate::services::history_export::CSV_HEADER;
+use crate::services::history_store::normalize_timestamp;
+use std::path::Path;
+
+/// Leading `CSV_HEADER` columns every CSV export has had; the rest are
+/// optional so older exports still import
+const REQUIRED_CSV_COLUMNS: usize = 10;
+
+/// Entries that parsed and validated, plus the number of rows that did not
+pub struct ParsedImport {
+    pub entries: Vec<HistoryEntry>,
+    pub invalid: usize,
+}
+
+/// Pick the import format from the file extension of an export
+pub fn format_for_path(path: &Path) -> Result<HistoryExportFormat, String> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "jsonl" | "ndjson" | "json" => Ok(HistoryExportFormat::Jsonl),
+        "csv" => Ok(HistoryExportFormat::Csv),
+        "md" | "markdown" => Err("Markdown reports cannot be imported; use a JSONL or CSV export".into()),
+        _ => Err(format!("Unsupported import file type: '{}'", path.display())),
+    }
+}
+
+pub fn parse(content: &str, format: HistoryExportFormat) -> Result<ParsedImport, String> {
+    match format {
+        HistoryExportFormat::Jsonl => Ok(parse_jsonl(content)),
+        HistoryExportFormat::Csv => parse_csv(content),
+        HistoryExportFormat::Markdown => Err("Markdown reports cannot be imported".into()),
+    }
+}
+
+fn parse_jsonl(content: &str) -> ParsedImport {
+    let mut parsed = ParsedImport {
+        entries: Vec::new(),
+        invalid: 0,
+    };
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str::<HistoryEntry>(line).map(normalize) {
+            Ok(Ok(entry)) => parsed.entries.push(entry),
+            _ => parsed.invalid += 1,
+        }
+    }
+    parsed
+}
+
+fn parse_csv(content: &str) -> Result<ParsedImport, String> {
+    let mut records = read_csv_records(content).into_iter();
+    let header = records.next().ok_or("CSV file is empty")?;
+
+    let columns = CSV_HEADER
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let position = header.iter().position(|h| h.trim() == *name);
+            if position.is_none() && i < REQUIRED_CSV_COLUMNS {
+                return Err(format!("CSV is missing the '{}' column", name));
+            }
+            Ok(position)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut parsed = ParsedImport {
+        entries: Vec::new(),
+        invalid: 0,
+    };
+    for record in records {
+        if record.len() != header.len() {
+            parsed.invalid += 1;
+            continue;
+        }
+        match entry_from_record(&record, &columns).map(normalize) {
+            Some(Ok(entry)) => parsed.entries.push(entry),
+            _ => parsed.invalid += 1,
+        }
+    }
+    Ok(parsed)
+}
+
+/// Build an entry from a CSV record; `columns` maps `CSV_HEADER` order to
+/// record indices, `None` for optional columns the file doesn't have
+fn entry_from_record(record: &[String], columns: &[Option<usize>]) -> Option<HistoryEntry> {
+    let field = |i: usize| columns[i].map(|c| record[c].clone()).unwrap_or_default();
+    let optional = |i: usize| Some(field(i)).filter(|v| !v.is_empty());
+    Some(HistoryEntry {
+        id: field(0),
+        timestamp: field(1),
+        action_name: field(2),
+        app_name: field(3),
+        original_text: field(4),
+        result_text: field(5),
+        provider: field(6),
+        model: field(7),
+        duration_ms: field(8).trim().parse().ok()?,
+        tokens_used: match field(9).trim() {
+            "" => None,
+            t => Some(t.parse().ok()?),
+        },
+        changes_count: match optional(10) {
+            None => None,
+            Some(c) => Some(c.trim().parse().ok()?),
+        },
+        app_bundle_id: optional(11),
+        window_title: optional(12),
+        steps: match optional(13) {
+            None => Vec::new(),
+            Some(json) => serde_json::from_str(&json).ok()?,
+        },
+        candidates: match optional(14) {
+            None => Vec::new(),
+            Some(json) => serde_json::from_str(&json).ok()?,
+        },
+    })
+}
+
+/// Split RFC 4180 CSV into records, honouring quoted separators and line breaks
+fn read_csv_records(content: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|r| !(r.len() == 1 && r[0].is_empty()));
+    records
+}
+
+/// Check that an imported entry has everything the history views rely on and
+/// rewrite its timestamp in the stored form (UTC, milliseconds), which
+/// filters, ordering and pruning compare as strings
+pub fn normalize(mut entry: HistoryEntry) -> Result<HistoryEntry, String> {
+    if entry.id.trim().is_empty() {
+        return Err("missing id".into());
+    }
+    entry.timestamp = normalize_timestamp(&entry.timestamp)?;
+    for (name, value) in [
+        ("action_name", &entry.action_name),
+        ("original_text", &entry.original_text),
+        ("result_text", &entry.result_text),
+        ("provider", &entry.provider),
+        ("model", &entry.model),
+    ] {
+        if value.trim().is_empty() {
+            return Err(format!("missing {}", name));
+        }
+    }
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::StepOutput;
+    use crate::services::history_export;
+
+    fn sample(id: &str) -> HistoryEntry {
+        HistoryEntry {
+            id: id.into(),
+            timestamp: "2026-01-01T10:00:00.000Z".into(),
+            action_name: "Proofread".into(),
+            original_text: "Line one, with comma\r\nLine \"two\"".into(),
+            result_text: "Fixed".into(),
+            provider: "OpenAI".into(),
+            model: "gpt-4o-mini".into(),
+            duration_ms: 120,
+            tokens_used: Some(7),
+            ..Default::default()
+        }
+    }
+
+    fn export(format: HistoryExportFormat) -> String {
+        let mut buf = Vec::new();
+        history_export::write_entries(&mut buf, &[sample("a"), sample("b")], format).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn csv_export_roundtrips() {
+        let parsed = parse(&export(HistoryExportFormat::Csv), HistoryExportFormat::Csv).unwrap();
+        assert_eq!(parsed.invalid, 0);
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.entries[0].original_text, sample("a").original_text);
+        assert_eq!(parsed.entries[1].tokens_used, Some(7));
+    }
+
+    #[test]
+    fn csv_keeps_every_field() {
+        let entry = HistoryEntry {
+            changes_count: Some(3),
+            app_bundle_id: Some("com.apple.mail".into()),
+            window_title: Some("Re: \"lunch\", today".into()),
+            steps: vec![StepOutput {
+                name: "Proofread".into(),
+                provider: "OpenAI".into(),
+                model: "gpt-4o-mini".into(),
+                text: "Fixed, long".into(),
+                duration_ms: 80,
+                tokens_used: Some(5),
+            }],
+            candidates: vec!["Fixed".into(), "Repaired".into()],
+            ..sample("a")
+        };
+        let mut buf = Vec::new();
+        let entries = std::slice::from_ref(&entry);
+        history_export::write_entries(&mut buf, entries, HistoryExportFormat::Csv).unwrap();
+        let parsed = parse(&String::from_utf8(buf).unwrap(), HistoryExportFormat::Csv).unwrap();
+        assert_eq!(parsed.entries.len(), 1);
+        let imported = &parsed.entries[0];
+        assert_eq!(imported.changes_count, entry.changes_count);
+        assert_eq!(imported.app_bundle_id, entry.app_bundle_id);
+        assert_eq!(imported.window_title, entry.window_title);
+        assert_eq!(imported.steps, entry.steps);
+        assert_eq!(imported.candidates, entry.candidates);
+    }
+
+    #[test]
+    fn csv_without_newer_columns_still_imports() {
+        let content = "id,timestamp,action_name,app_name,original_text,result_text,provider,model,duration_ms,tokens_used\r\n\
+                       a,2026-01-01T10:00:00.000Z,Proofread,,teh,the,OpenAI,gpt-4o-mini,120,\r\n";
+        let parsed = parse(content, HistoryExportFormat::Csv).unwrap();
+        assert_eq!(parsed.invalid, 0);
+        assert!(parsed.entries[0].app_bundle_id.is_none());
+        assert!(parsed.entries[0].steps.is_empty());
+    }
+
+    #[test]
+    fn jsonl_export_roundtrips() {
+        let parsed = parse(&export(HistoryExportFormat::Jsonl), HistoryExportFormat::Jsonl).unwrap();
+        assert_eq!(parsed.invalid, 0);
+        assert_eq!(parsed.entries[1].id, "b");
+    }
+
+    #[test]
+    fn invalid_rows_are_counted() {
+        let mut content = export(HistoryExportFormat::Jsonl);
+        content.push_str("not json\n");
+        content.push_str(&serde_json::to_string(&HistoryEntry {
+            timestamp: "yesterday".into(),
+            ..sample("c")
+        }).unwrap());
+        let parsed = parse(&content, HistoryExportFormat::Jsonl).unwrap();
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.invalid, 2);
+    }
+
+    #[test]
+    fn timestamps_are_stored_in_utc_milliseconds() {
+        let content = serde_json::to_string(&HistoryEntry {
+            timestamp: "2026-01-01T12:00:00+02:00".into(),
+            ..sample("a")
+        })
+        .unwrap();
+        let parsed = parse(&content, HistoryExportFormat::Jsonl).unwrap();
+        assert_eq!(parsed.entries[0].timestamp, "2026-01-01T10:00:00.000Z");
+    }
+
+    #[test]
+    fn csv_requires_known_header() {
+        assert!(parse("foo,bar\r\n1,2\r\n", HistoryExportFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn format_is_picked_from_extension() {
+        assert!(matches!(format_for_path(Path::new("a.CSV")), Ok(HistoryExportFormat::Csv)));
+        assert!(matches!(format_for_path(Path::new("a.jsonl")), Ok(HistoryExportFormat::Jsonl)));
+        assert!(format_for_path(Path::new("a.md")).is_err());
+    }
+}
@@ -0,0 +1,894 @@
+use crate::models::{HistoryEntry, HistoryFilter, PruneReport, RetentionSettings};
+use crate::services::{key_storage, text_diff};
+use aes_gcm::Aes256Gcm;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use rusqlite::types::{Type, Value, ValueRef};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row, Transaction};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub const DB_FILENAME: &str = "omni_text_history.db";
+
+/// Deleting at least this many rows in one prune triggers a `VACUUM`
+const VACUUM_THRESHOLD: usize = 500;
+
+struct Migration {
+    version: i64,
+    description: &'static str,
+    step: MigrationStep,
+}
+
+enum MigrationStep {
+    Sql(&'static str),
+    /// Data migrations that need the history cipher
+    Rust(fn(&Transaction, &Aes256Gcm) -> Result<(), String>),
+}
+
+/// Schema migrations, applied in order and tracked via `PRAGMA user_version`.
+/// Version 1 matches the table previously created by tauri-plugin-sql, so
+/// existing databases upgrade in place.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create history table",
+        step: MigrationStep::Sql("CREATE TABLE IF NOT EXISTS history (
+            id TEXT PRIMARY KEY,
+            timestamp TEXT NOT NULL,
+            action_name TEXT NOT NULL,
+            app_name TEXT NOT NULL DEFAULT '',
+            original_text TEXT NOT NULL,
+            result_text TEXT NOT NULL,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            duration_ms INTEGER NOT NULL DEFAULT 0,
+            tokens_used INTEGER
+        );"),
+    },
+    Migration {
+        version: 2,
+        description: "add history filter indexes",
+        step: MigrationStep::Sql("CREATE INDEX IF NOT EXISTS idx_history_timestamp ON history (timestamp);
+            CREATE INDEX IF NOT EXISTS idx_history_action ON history (action_name, timestamp);
+            CREATE INDEX IF NOT EXISTS idx_history_provider_model ON history (provider, model, timestamp);
+            CREATE INDEX IF NOT EXISTS idx_history_app ON history (app_name, timestamp);
+            CREATE INDEX IF NOT EXISTS idx_history_duration ON history (duration_ms);"),
+    },
+    Migration {
+        version: 3,
+        description: "encrypt original and result text",
+        step: MigrationStep::Rust(encrypt_plaintext_rows),
+    },
+    Migration {
+        version: 4,
+        description: "add changes_count column",
+        step: MigrationStep::Rust(add_changes_count),
+    },
+    Migration {
+        version: 5,
+        description: "add source app columns",
+        step: MigrationStep::Sql("ALTER TABLE history ADD COLUMN app_bundle_id TEXT;
+            ALTER TABLE history ADD COLUMN window_title BLOB;
+            CREATE INDEX IF NOT EXISTS idx_history_app_bundle ON history (app_bundle_id, timestamp);"),
+    },
+    Migration {
+        version: 6,
+        description: "add steps column",
+        step: MigrationStep::Sql("ALTER TABLE history ADD COLUMN steps BLOB;"),
+    },
+    Migration {
+        version: 7,
+        description: "add candidates column",
+        step: MigrationStep::Sql("ALTER TABLE history ADD COLUMN candidates BLOB;"),
+    },
+];
+
+const COLUMNS: &str = "id, timestamp, action_name, app_name, original_text, result_text, \
+                       provider, model, duration_ms, tokens_used, changes_count, \
+                       app_bundle_id, window_title, steps, candidates";
+
+/// SQLite-backed history log, shared with the frontend through Tauri commands.
+///
+/// `original_text`, `result_text`, `window_title`, `steps` and `candidates`
+/// (as JSON) are stored as AES-256-GCM encrypted BLOBs (`nonce || ciphertext`) and decrypted
+/// transparently on read.
+pub struct HistoryStore {
+    conn: Mutex<Connection>,
+    cipher: Aes256Gcm,
+}
+
+impl HistoryStore {
+    pub fn open(app_dir: PathBuf, cipher: Aes256Gcm) -> Result<Self, String> {
+        let conn = Connection::open(app_dir.join(DB_FILENAME))
+            .map_err(|e| format!("Failed to open history database: {}", e))?;
+        Self::with_connection(conn, cipher)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open history database: {}", e))?;
+        Self::with_connection(conn, tests::test_cipher())
+    }
+
+    fn with_connection(mut conn: Connection, cipher: Aes256Gcm) -> Result<Self, String> {
+        Self::migrate(&mut conn, &cipher)?;
+        Ok(HistoryStore {
+            conn: Mutex::new(conn),
+            cipher,
+        })
+    }
+
+    fn migrate(conn: &mut Connection, cipher: &Aes256Gcm) -> Result<(), String> {
+        let current: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read schema version: {}", e))?;
+
+        for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+            let tx = conn
+                .transaction()
+                .map_err(|e| format!("Failed to start migration: {}", e))?;
+            let result = match migration.step {
+                MigrationStep::Sql(sql) => tx.execute_batch(sql).map_err(|e| e.to_string()),
+                MigrationStep::Rust(apply) => apply(&tx, cipher),
+            };
+            result.map_err(|e| {
+                format!(
+                    "Migration {} ({}) failed: {}",
+                    migration.version, migration.description, e
+                )
+            })?;
+            tx.pragma_update(None, "user_version", migration.version)
+                .map_err(|e| format!("Failed to update schema version: {}", e))?;
+            tx.commit()
+                .map_err(|e| format!("Failed to commit migration: {}", e))?;
+        }
+        Ok(())
+    }
+
+    pub fn insert(&self, entry: &HistoryEntry) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        insert_entry(&conn, &self.cipher, "INSERT", entry)
+            .map_err(|e| format!("Failed to insert history entry: {}", e))?;
+        Ok(())
+    }
+
+    /// Insert entries in one transaction, ignoring ids that already exist.
+    /// Returns `(inserted, skipped)`.
+    pub fn insert_many(&self, entries: &[HistoryEntry]) -> Result<(usize, usize), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start import: {}", e))?;
+        let mut inserted = 0;
+        for entry in entries {
+            inserted += insert_entry(&tx, &self.cipher, "INSERT OR IGNORE", entry)
+                .map_err(|e| format!("Failed to import history entry: {}", e))?;
+        }
+        tx.commit()
+            .map_err(|e| format!("Failed to commit import: {}", e))?;
+        Ok((inserted, entries.len() - inserted))
+    }
+
+    pub fn search(&self, filter: &HistoryFilter, limit: u32) -> Result<Vec<HistoryEntry>, String> {
+        let mut clauses: Vec<&str> = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+
+        for (column, value) in [
+            ("action_name = ?", &filter.action_name),
+            ("provider = ?", &filter.provider),
+            ("model = ?", &filter.model),
+        ] {
+            if let Some(v) = non_empty(value) {
+                clauses.push(column);
+                values.push(Value::Text(v.to_string()));
+            }
+        }
+        // The app filter takes either the display name or the bundle id
+        if let Some(app) = non_empty(&filter.app_name) {
+            clauses.push("(app_name = ? OR app_bundle_id = ?)");
+            values.push(Value::Text(app.to_string()));
+            values.push(Value::Text(app.to_string()));
+        }
+        if let Some(from) = non_empty(&filter.from) {
+            clauses.push("timestamp >= ?");
+            values.push(Value::Text(normalize_timestamp(from)?));
+        }
+        if let Some(to) = non_empty(&filter.to) {
+            clauses.push("timestamp <= ?");
+            values.push(Value::Text(normalize_timestamp(to)?));
+        }
+        if let Some(min) = filter.min_duration_ms {
+            clauses.push("duration_ms >= ?");
+            values.push(Value::Integer(min as i64));
+        }
+
+        let where_sql = if clauses.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", clauses.join(" AND "))
+        };
+
+        // Text columns are encrypted, so free-text search runs after
+        // decryption and the limit can only be pushed into SQL without it.
+        let query = non_empty(&filter.query).map(str::to_lowercase);
+        let limit_sql = if query.is_none() {
+            values.push(Value::Integer(limit as i64));
+            "LIMIT ?"
+        } else {
+            ""
+        };
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM history {} ORDER BY timestamp DESC {}",
+                COLUMNS, where_sql, limit_sql
+            ))
+            .map_err(|e| format!("Failed to prepare history query: {}", e))?;
+        let rows = stmt
+            .query_map(params_from_iter(values), |row| row_to_entry(row, &self.cipher))
+            .map_err(|e| format!("Failed to query history: {}", e))?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let entry = match row {
+                Ok(entry) => entry,
+                // A row that no longer decrypts (say, written under another
+                // machine's key) is skipped rather than hiding every other row
+                Err(e @ rusqlite::Error::FromSqlConversionFailure(_, Type::Blob, _)) => {
+                    eprintln!("Skipping unreadable history row: {}", e);
+                    continue;
+                }
+                Err(e) => return Err(format!("Failed to read history row: {}", e)),
+            };
+            if query.as_deref().is_none_or(|q| matches_query(&entry, q)) {
+                entries.push(entry);
+                if entries.len() >= limit as usize {
+                    break;
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<HistoryEntry>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {} FROM history WHERE id = ?1", COLUMNS),
+            params![id],
+            |row| row_to_entry(row, &self.cipher),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to read history entry: {}", e))
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM history WHERE id = ?1", params![id])
+            .map_err(|e| format!("Failed to delete history entry: {}", e))?;
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM history", [])
+            .map_err(|e| format!("Failed to clear history: {}", e))?;
+        Ok(())
+    }
+
+    /// Apply retention limits, oldest entries first. Limits are applied in
+    /// order (age, row count, database size) inside one transaction, which is
+    /// rolled back on a dry run so the report matches a real prune exactly.
+    pub fn prune(
+        &self,
+        settings: &RetentionSettings,
+        now: DateTime<Utc>,
+        dry_run: bool,
+    ) -> Result<PruneReport, String> {
+        let mut conn = self.conn.lock().unwrap();
+        let db_size_bytes = database_size(&conn)?;
+        let mut report = PruneReport {
+            dry_run,
+            db_size_bytes,
+            ..Default::default()
+        };
+
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start prune: {}", e))?;
+
+        if let Some(days) = settings.max_age_days {
+            let cutoff = (now - Duration::days(days as i64))
+                .to_rfc3339_opts(SecondsFormat::Millis, true);
+            report.removed_by_age = tx
+                .execute("DELETE FROM history WHERE timestamp < ?1", params![cutoff])
+                .map_err(|e| format!("Failed to prune by age: {}", e))?;
+        }
+
+        if let Some(max_rows) = settings.max_rows {
+            report.removed_by_count = delete_oldest_beyond(&tx, max_rows)
+                .map_err(|e| format!("Failed to prune by row count: {}", e))?;
+        }
+
+        if let Some(max_mb) = settings.max_db_size_mb {
+            let max_bytes = max_mb * 1024 * 1024;
+            if db_size_bytes > max_bytes {
+                // Freed pages are only returned after VACUUM, so estimate the
+                // rows to drop from the average row footprint before pruning.
+                let rows: u64 = tx
+                    .query_row("SELECT COUNT(*) FROM history", [], |r| r.get::<_, i64>(0))
+                    .map_err(|e| format!("Failed to count history: {}", e))?
+                    as u64;
+                let removed_so_far = (report.removed_by_age + report.removed_by_count) as u64;
+                let original_rows = rows + removed_so_far;
+                if let Some(avg_row_bytes) = db_size_bytes.checked_div(original_rows) {
+                    let avg_row_bytes = avg_row_bytes.max(1);
+                    let excess_rows = (db_size_bytes - max_bytes).div_ceil(avg_row_bytes);
+                    let keep = original_rows.saturating_sub(excess_rows).min(rows);
+                    report.removed_by_size = delete_oldest_beyond(&tx, keep)
+                        .map_err(|e| format!("Failed to prune by size: {}", e))?;
+                }
+            }
+        }
+
+        if dry_run {
+            tx.rollback()
+                .map_err(|e| format!("Failed to roll back prune: {}", e))?;
+            return Ok(report);
+        }
+        tx.commit()
+            .map_err(|e| format!("Failed to commit prune: {}", e))?;
+
+        if report.total_removed() >= VACUUM_THRESHOLD || report.removed_by_size > 0 {
+            conn.execute_batch("VACUUM")
+                .map_err(|e| format!("Failed to vacuum history database: {}", e))?;
+            report.vacuumed = true;
+            report.db_size_bytes = database_size(&conn)?;
+        }
+        Ok(report)
+    }
+}
+
+/// Delete everything but the newest `keep` rows
+fn delete_oldest_beyond(conn: &Connection, keep: u64) -> rusqlite::Result<usize> {
+    conn.execute(
+        "DELETE FROM history WHERE id IN (
+            SELECT id FROM history ORDER BY timestamp DESC LIMIT -1 OFFSET ?1
+        )",
+        params![keep as i64],
+    )
+}
+
+fn database_size(conn: &Connection) -> Result<u64, String> {
+    conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |r| r.get::<_, i64>(0),
+    )
+    .map(|size| size as u64)
+    .map_err(|e| format!("Failed to read database size: {}", e))
+}
+
+fn insert_entry(
+    conn: &Connection,
+    cipher: &Aes256Gcm,
+    verb: &str,
+    entry: &HistoryEntry,
+) -> Result<usize, String> {
+    let original = key_storage::encrypt(cipher, entry.original_text.as_bytes())?;
+    let result = key_storage::encrypt(cipher, entry.result_text.as_bytes())?;
+    let window_title = entry
+        .window_title
+        .as_ref()
+        .map(|title| key_storage::encrypt(cipher, title.as_bytes()))
+        .transpose()?;
+    let steps = encrypt_list(cipher, &entry.steps, "steps")?;
+    let candidates = encrypt_list(cipher, &entry.candidates, "candidates")?;
+    let changes = text_diff::changes_count(&entry.original_text, &entry.result_text);
+    conn.prepare_cached(&format!(
+        "{} INTO history ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+        verb, COLUMNS
+    ))
+    .and_then(|mut stmt| stmt.execute(params![
+        entry.id,
+        entry.timestamp,
+        entry.action_name,
+        entry.app_name,
+        original,
+        result,
+        entry.provider,
+        entry.model,
+        entry.duration_ms as i64,
+        entry.tokens_used.map(|t| t as i64),
+        changes,
+        entry.app_bundle_id,
+        window_title,
+        steps,
+        candidates,
+    ]))
+    .map_err(|e| e.to_string())
+}
+
+fn row_to_entry(row: &Row, cipher: &Aes256Gcm) -> rusqlite::Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        action_name: row.get(2)?,
+        app_name: row.get(3)?,
+        original_text: decrypt_column(row, 4, cipher)?,
+        result_text: decrypt_column(row, 5, cipher)?,
+        provider: row.get(6)?,
+        model: row.get(7)?,
+        duration_ms: row.get::<_, i64>(8)? as u64,
+        tokens_used: row.get::<_, Option<i64>>(9)?.map(|t| t as u64),
+        changes_count: row.get(10)?,
+        app_bundle_id: row.get(11)?,
+        window_title: match row.get_ref(12)? {
+            ValueRef::Null => None,
+            _ => Some(decrypt_column(row, 12, cipher)?),
+        },
+        steps: decrypt_list(row, 13, cipher)?,
+        candidates: decrypt_list(row, 14, cipher)?,
+    })
+}
+
+/// Encrypt `items` as JSON, or store NULL when there are none
+fn encrypt_list<T: Serialize>(
+    cipher: &Aes256Gcm,
+    items: &[T],
+    what: &str,
+) -> Result<Option<Vec<u8>>, String> {
+    if items.is_empty() {
+        return Ok(None);
+    }
+    let json =
+        serde_json::to_vec(items).map_err(|e| format!("Failed to serialize {}: {}", what, e))?;
+    key_storage::encrypt(cipher, &json).map(Some)
+}
+
+/// Read a list written by `encrypt_list`; NULL is an empty list
+fn decrypt_list<T: DeserializeOwned>(
+    row: &Row,
+    idx: usize,
+    cipher: &Aes256Gcm,
+) -> rusqlite::Result<Vec<T>> {
+    match row.get_ref(idx)? {
+        ValueRef::Null => Ok(Vec::new()),
+        _ => serde_json::from_str(&decrypt_column(row, idx, cipher)?)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, Type::Blob, e.into())),
+    }
+}
+
+/// Read an encrypted text column. Plain TEXT values are passed through so a
+/// row written by an older build is still readable.
+fn decrypt_column(row: &Row, idx: usize, cipher: &Aes256Gcm) -> rusqlite::Result<String> {
+    match row.get::<_, Value>(idx)? {
+        Value::Blob(data) => key_storage::decrypt(cipher, &data)
+            .and_then(|plain| String::from_utf8(plain).map_err(|e| e.to_string()))
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, Type::Blob, e.into())),
+        Value::Text(text) => Ok(text),
+        other => Err(rusqlite::Error::InvalidColumnType(
+            idx,
+            "text".into(),
+            other.data_type(),
+        )),
+    }
+}
+
+/// Case-insensitive match against the decrypted text, including step
+/// outputs and candidates, and the action name
+fn matches_query(entry: &HistoryEntry, query: &str) -> bool {
+    [&entry.original_text, &entry.result_text, &entry.action_name]
+        .into_iter()
+        .chain(entry.steps.iter().map(|step| &step.text))
+        .chain(&entry.candidates)
+        .any(|field| field.to_lowercase().contains(query))
+}
+
+/// Migration 3: encrypt rows written before history encryption existed
+fn encrypt_plaintext_rows(tx: &Transaction, cipher: &Aes256Gcm) -> Result<(), String> {
+    let rows = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT id, original_text, result_text FROM history
+                 WHERE typeof(original_text) = 'text' OR typeof(result_text) = 'text'",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Value>(1)?, row.get::<_, Value>(2)?)))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?
+    };
+
+    let encrypt_value = |value: Value| -> Result<Value, String> {
+        match value {
+            Value::Text(text) => Ok(Value::Blob(key_storage::encrypt(cipher, text.as_bytes())?)),
+            other => Ok(other),
+        }
+    };
+    for (id, original, result) in rows {
+        tx.execute(
+            "UPDATE history SET original_text = ?1, result_text = ?2 WHERE id = ?3",
+            params![encrypt_value(original)?, encrypt_value(result)?, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Migration 4: add `changes_count` and backfill it from the decrypted text
+fn add_changes_count(tx: &Transaction, cipher: &Aes256Gcm) -> Result<(), String> {
+    tx.execute_batch("ALTER TABLE history ADD COLUMN changes_count INTEGER;")
+        .map_err(|e| e.to_string())?;
+
+    let counts = {
+        let mut stmt = tx
+            .prepare("SELECT id, original_text, result_text FROM history")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                let original = decrypt_column(row, 1, cipher)?;
+                let result = decrypt_column(row, 2, cipher)?;
+                Ok((row.get::<_, String>(0)?, text_diff::changes_count(&original, &result)))
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?
+    };
+    for (id, count) in counts {
+        tx.execute(
+            "UPDATE history SET changes_count = ?1 WHERE id = ?2",
+            params![count, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Timestamps are stored as JS `toISOString()` output (UTC, millisecond
+/// precision), so bounds are rewritten into the same shape to keep string
+/// comparison in SQLite correct.
+pub(crate) fn normalize_timestamp(value: &str) -> Result<String, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| {
+            dt.with_timezone(&Utc)
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+        })
+        .map_err(|e| format!("Invalid timestamp '{}': {}", value, e))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::models::StepOutput;
+    use aes_gcm::KeyInit;
+
+    pub fn test_cipher() -> Aes256Gcm {
+        Aes256Gcm::new(&[7u8; 32].into())
+    }
+
+    fn entry(id: &str, timestamp: &str, action: &str, provider: &str, duration_ms: u64) -> HistoryEntry {
+        HistoryEntry {
+            id: id.into(),
+            timestamp: timestamp.into(),
+            action_name: action.into(),
+            app_name: "Mail".into(),
+            original_text: format!("original {}", id),
+            result_text: format!("result {}", id),
+            provider: provider.into(),
+            model: "gpt-4o-mini".into(),
+            duration_ms,
+            tokens_used: Some(42),
+            app_bundle_id: Some("com.apple.mail".into()),
+            window_title: Some(format!("Re: {}", id)),
+            ..Default::default()
+        }
+    }
+
+    fn seeded_store() -> HistoryStore {
+        let store = HistoryStore::open_in_memory().unwrap();
+        store.insert(&entry("a", "2026-01-01T10:00:00.000Z", "Proofread", "OpenAI", 300)).unwrap();
+        store.insert(&entry("b", "2026-01-02T10:00:00.000Z", "Rewrite", "OpenAI", 1500)).unwrap();
+        store.insert(&entry("c", "2026-01-03T10:00:00.000Z", "Proofread", "Groq", 90)).unwrap();
+        store
+    }
+
+    fn ids(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.id.as_str()).collect()
+    }
+
+    #[test]
+    fn migrations_set_user_version() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        let conn = store.conn.lock().unwrap();
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |r| r.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.last().unwrap().version);
+    }
+
+    #[test]
+    fn empty_filter_returns_newest_first() {
+        let store = seeded_store();
+        let result = store.search(&HistoryFilter::default(), 100).unwrap();
+        assert_eq!(ids(&result), vec!["c", "b", "a"]);
+        assert_eq!(result[0].changes_count, Some(1));
+    }
+
+    #[test]
+    fn filters_are_combined() {
+        let store = seeded_store();
+        let filter = HistoryFilter {
+            action_name: Some("Proofread".into()),
+            provider: Some("OpenAI".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&store.search(&filter, 100).unwrap()), vec!["a"]);
+    }
+
+    #[test]
+    fn app_filter_matches_name_or_bundle_id() {
+        let store = seeded_store();
+        store
+            .insert(&HistoryEntry {
+                app_name: "Slack".into(),
+                app_bundle_id: Some("com.tinyspeck.slackmacgap".into()),
+                window_title: None,
+                ..entry("d", "2026-01-04T10:00:00.000Z", "Rewrite", "OpenAI", 200)
+            })
+            .unwrap();
+        let by = |app: &str| HistoryFilter {
+            app_name: Some(app.into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&store.search(&by("Slack"), 100).unwrap()), vec!["d"]);
+        assert_eq!(
+            ids(&store.search(&by("com.tinyspeck.slackmacgap"), 100).unwrap()),
+            vec!["d"]
+        );
+        assert_eq!(store.search(&by("com.apple.mail"), 100).unwrap().len(), 3);
+
+        let slack = &store.search(&by("Slack"), 1).unwrap()[0];
+        assert_eq!(slack.window_title, None);
+        let mail = &store.search(&by("Mail"), 1).unwrap()[0];
+        assert_eq!(mail.window_title.as_deref(), Some("Re: c"));
+    }
+
+    #[test]
+    fn date_range_accepts_offsets() {
+        let store = seeded_store();
+        let filter = HistoryFilter {
+            from: Some("2026-01-02T11:00:00+01:00".into()),
+            to: Some("2026-01-02T23:59:59Z".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&store.search(&filter, 100).unwrap()), vec!["b"]);
+    }
+
+    #[test]
+    fn min_duration_and_query() {
+        let store = seeded_store();
+        let filter = HistoryFilter {
+            min_duration_ms: Some(200),
+            query: Some("result".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&store.search(&filter, 100).unwrap()), vec!["b", "a"]);
+    }
+
+    #[test]
+    fn insert_many_skips_existing_ids() {
+        let store = seeded_store();
+        let batch = vec![
+            entry("a", "2026-01-05T10:00:00.000Z", "Proofread", "OpenAI", 10),
+            entry("d", "2026-01-05T10:00:00.000Z", "Proofread", "OpenAI", 10),
+            entry("d", "2026-01-06T10:00:00.000Z", "Proofread", "OpenAI", 10),
+        ];
+        assert_eq!(store.insert_many(&batch).unwrap(), (1, 2));
+        assert_eq!(store.search(&HistoryFilter::default(), 100).unwrap().len(), 4);
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-01-04T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn prune_by_age_and_count() {
+        let store = seeded_store();
+        let settings = RetentionSettings {
+            max_age_days: Some(2),
+            max_rows: Some(1),
+            max_db_size_mb: None,
+        };
+        let report = store.prune(&settings, now(), false).unwrap();
+        assert_eq!(report.removed_by_age, 1);
+        assert_eq!(report.removed_by_count, 1);
+        assert_eq!(ids(&store.search(&HistoryFilter::default(), 100).unwrap()), vec!["c"]);
+    }
+
+    #[test]
+    fn prune_dry_run_keeps_rows() {
+        let store = seeded_store();
+        let settings = RetentionSettings {
+            max_rows: Some(0),
+            ..Default::default()
+        };
+        let report = store.prune(&settings, now(), true).unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.removed_by_count, 3);
+        assert_eq!(store.search(&HistoryFilter::default(), 100).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn prune_by_size_vacuums() {
+        let store = seeded_store();
+        let settings = RetentionSettings {
+            max_db_size_mb: Some(0),
+            ..Default::default()
+        };
+        let report = store.prune(&settings, now(), false).unwrap();
+        assert_eq!(report.removed_by_size, 3);
+        assert!(report.vacuumed);
+    }
+
+    #[test]
+    fn text_columns_are_encrypted_at_rest() {
+        let store = seeded_store();
+        let conn = store.conn.lock().unwrap();
+        let (kind, raw): (String, Vec<u8>) = conn
+            .query_row(
+                "SELECT typeof(original_text), original_text FROM history WHERE id = 'a'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(kind, "blob");
+        assert!(!String::from_utf8_lossy(&raw).contains("original a"));
+
+        let title: Vec<u8> = conn
+            .query_row("SELECT window_title FROM history WHERE id = 'a'", [], |r| r.get(0))
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&title).contains("Re: a"));
+    }
+
+    #[test]
+    fn migration_encrypts_existing_rows() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        for migration in &MIGRATIONS[..2] {
+            if let MigrationStep::Sql(sql) = migration.step {
+                conn.execute_batch(sql).unwrap();
+            }
+        }
+        conn.pragma_update(None, "user_version", 2).unwrap();
+        conn.execute(
+            "INSERT INTO history (id, timestamp, action_name, original_text, result_text, provider, model)
+             VALUES ('old', '2025-12-01T00:00:00.000Z', 'Proofread', 'teh text', 'the text', 'OpenAI', 'gpt-4o-mini')",
+            [],
+        )
+        .unwrap();
+
+        HistoryStore::migrate(&mut conn, &test_cipher()).unwrap();
+        let store = HistoryStore {
+            conn: Mutex::new(conn),
+            cipher: test_cipher(),
+        };
+        let entries = store.search(&HistoryFilter::default(), 10).unwrap();
+        assert_eq!(entries[0].original_text, "teh text");
+        assert_eq!(entries[0].changes_count, Some(1));
+        let conn = store.conn.lock().unwrap();
+        let kind: String = conn
+            .query_row("SELECT typeof(result_text) FROM history", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(kind, "blob");
+    }
+
+    #[test]
+    fn query_matches_decrypted_text_case_insensitively() {
+        let store = seeded_store();
+        let filter = HistoryFilter {
+            query: Some("RESULT B".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&store.search(&filter, 100).unwrap()), vec!["b"]);
+    }
+
+    #[test]
+    fn search_skips_rows_that_fail_to_decrypt() {
+        let store = seeded_store();
+        store
+            .conn
+            .lock()
+            .unwrap()
+            .execute("UPDATE history SET result_text = x'00112233' WHERE id = 'b'", [])
+            .unwrap();
+
+        let all = store.search(&HistoryFilter::default(), 100).unwrap();
+        assert_eq!(ids(&all), vec!["c", "a"]);
+        let filter = HistoryFilter {
+            query: Some("original".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&store.search(&filter, 100).unwrap()), vec!["c", "a"]);
+    }
+
+    #[test]
+    fn steps_round_trip_encrypted_and_are_searchable() {
+        let store = seeded_store();
+        let step = |name: &str, text: &str| StepOutput {
+            name: name.into(),
+            provider: "OpenAI".into(),
+            model: "gpt-4o-mini".into(),
+            text: text.into(),
+            duration_ms: 100,
+            tokens_used: Some(10),
+        };
+        let steps = vec![step("Proofread", "fixed draft"), step("Shorten", "short draft")];
+        store
+            .insert(&HistoryEntry {
+                steps: steps.clone(),
+                ..entry("d", "2026-01-04T10:00:00.000Z", "Polish", "OpenAI", 200)
+            })
+            .unwrap();
+
+        let filter = HistoryFilter {
+            query: Some("FIXED DRAFT".into()),
+            ..Default::default()
+        };
+        let found = store.search(&filter, 100).unwrap();
+        assert_eq!(ids(&found), vec!["d"]);
+        assert_eq!(found[0].steps, steps);
+        assert!(store.search(&HistoryFilter::default(), 100).unwrap()[1..]
+            .iter()
+            .all(|e| e.steps.is_empty()));
+
+        let conn = store.conn.lock().unwrap();
+        let raw: Vec<u8> = conn
+            .query_row("SELECT steps FROM history WHERE id = 'd'", [], |r| r.get(0))
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("fixed draft"));
+    }
+
+    #[test]
+    fn candidates_round_trip_and_are_searchable() {
+        let store = seeded_store();
+        let candidates = vec!["result d".to_string(), "another take".to_string()];
+        store
+            .insert(&HistoryEntry {
+                candidates: candidates.clone(),
+                ..entry("d", "2026-01-04T10:00:00.000Z", "Polish", "OpenAI", 200)
+            })
+            .unwrap();
+
+        let filter = HistoryFilter {
+            query: Some("ANOTHER TAKE".into()),
+            ..Default::default()
+        };
+        let found = store.search(&filter, 100).unwrap();
+        assert_eq!(ids(&found), vec!["d"]);
+        assert_eq!(found[0].candidates, candidates);
+    }
+
+    #[test]
+    fn get_returns_one_decrypted_entry() {
+        let store = seeded_store();
+        let found = store.get("b").unwrap().unwrap();
+        assert_eq!(found.original_text, "original b");
+        assert_eq!(found.result_text, "result b");
+        assert!(store.get("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_timestamp_is_rejected() {
+        let store = seeded_store();
+        let filter = HistoryFilter {
+            from: Some("yesterday".into()),
+            ..Default::default()
+        };
+        assert!(store.search(&filter, 100).is_err());
+    }
+}
@@ -7,6 +7,7 @@ use sha2::Sha256;
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+#[cfg(target_os = "macos")]
 use std::process::Command;
 use std::sync::Mutex;
 
@@ -14,12 +15,17 @@ const HKDF_SALT: &[u8] = b"com.addison.omni-text.keys.v1";
 const KEYS_FILENAME: &str = "api_keys.enc";
 const NONCE_LEN: usize = 12;
 
+/// HKDF info labels; each label yields an independent key from the same machine secret
+const API_KEYS_INFO: &[u8] = b"encryption-key";
+pub const HISTORY_INFO: &[u8] = b"history-encryption-key";
+
 pub struct KeyStorage {
     keys: Mutex<HashMap<String, String>>,
     file_path: PathBuf,
     cipher: Aes256Gcm,
 }
 
+#[cfg(target_os = "macos")]
 fn get_machine_uuid() -> Result<String, String> {
     let output = Command::new("ioreg")
         .args(["-rd1", "-c", "IOPlatformExpertDevice"])
@@ -37,26 +43,68 @@ fn get_machine_uuid() -> Result<String, String> {
     Err("IOPlatformUUID not found in ioreg output".to_string())
 }
 
-fn derive_key(uuid: &str) -> Key<Aes256Gcm> {
+#[cfg(target_os = "linux")]
+fn get_machine_uuid() -> Result<String, String> {
+    // systemd writes /etc/machine-id; older distros only have the D-Bus copy
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|path| {
+            std::fs::read_to_string(path)
+                .ok()
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+        })
+        .ok_or_else(|| "machine-id not found".to_string())
+}
+
+fn derive_key(uuid: &str, info: &[u8]) -> Key<Aes256Gcm> {
     let hk = Hkdf::<Sha256>::new(Some(HKDF_SALT), uuid.as_bytes());
     let mut key_bytes = [0u8; 32];
-    hk.expand(b"encryption-key", &mut key_bytes)
+    hk.expand(info, &mut key_bytes)
         .expect("HKDF expand failed");
     key_bytes.into()
 }
 
+/// Build a cipher bound to this machine for the given HKDF info label
+pub fn machine_cipher(info: &[u8]) -> Result<Aes256Gcm, String> {
+    let uuid = get_machine_uuid()?;
+    Ok(Aes256Gcm::new(&derive_key(&uuid, info)))
+}
+
+/// Encrypt with a fresh random nonce, returning `nonce || ciphertext`
+pub fn encrypt(cipher: &Aes256Gcm, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+
+    let mut data = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+/// Decrypt data produced by [`encrypt`]
+pub fn decrypt(cipher: &Aes256Gcm, data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < NONCE_LEN {
+        return Err("Encrypted data too short".to_string());
+    }
+
+    let (nonce_bytes, ciphertext) = data.split_at(NONCE_LEN);
+    let nonce = aes_gcm::Nonce::from_slice(nonce_bytes);
+
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|e| format!("Decryption failed: {}", e))
+}
+
 impl KeyStorage {
     pub fn new(app_dir: PathBuf) -> Self {
-        let uuid = get_machine_uuid().expect("Failed to get machine UUID");
-        let key = derive_key(&uuid);
-        let cipher = Aes256Gcm::new(&key);
+        let cipher = machine_cipher(API_KEYS_INFO).expect("Failed to get machine UUID");
         let file_path = app_dir.join(KEYS_FILENAME);
 
         let keys = if file_path.exists() {
-            match Self::load_from_file(&file_path, &cipher) {
-                Ok(map) => map,
-                Err(_) => HashMap::new(),
-            }
+            Self::load_from_file(&file_path, &cipher).unwrap_or_default()
         } else {
             HashMap::new()
         };
@@ -73,17 +121,7 @@ impl KeyStorage {
         cipher: &Aes256Gcm,
     ) -> Result<HashMap<String, String>, String> {
         let data = fs::read(path).map_err(|e| format!("Failed to read keys file: {}", e))?;
-
-        if data.len() < NONCE_LEN {
-            return Err("Keys file too short".to_string());
-        }
-
-        let (nonce_bytes, ciphertext) = data.split_at(NONCE_LEN);
-        let nonce = aes_gcm::Nonce::from_slice(nonce_bytes);
-
-        let plaintext = cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| format!("Decryption failed: {}", e))?;
+        let plaintext = decrypt(cipher, &data)?;
 
         let json_str = String::from_utf8(plaintext)
             .map_err(|e| format!("Invalid UTF-8 in decrypted data: {}", e))?;
@@ -95,15 +133,7 @@ impl KeyStorage {
         let json = serde_json::to_string(keys)
             .map_err(|e| format!("Failed to serialize keys: {}", e))?;
 
-        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-        let ciphertext = self
-            .cipher
-            .encrypt(&nonce, json.as_bytes())
-            .map_err(|e| format!("Encryption failed: {}", e))?;
-
-        let mut data = Vec::with_capacity(NONCE_LEN + ciphertext.len());
-        data.extend_from_slice(&nonce);
-        data.extend_from_slice(&ciphertext);
+        let data = encrypt(&self.cipher, json.as_bytes())?;
 
         // Atomic write: write to .tmp then rename
         let tmp_path = self.file_path.with_extension("tmp");
@@ -0,0 +1,60 @@
+/// The user's preferred language as a BCP 47 tag such as "en-US", for the
+/// `{{language}}` template variable
+#[cfg(target_os = "macos")]
+pub fn preferred_language() -> Option<String> {
+    use crate::services::objc::{class, rust_string, send, send_index, send_usize, with_pool};
+
+    with_pool(|| unsafe {
+        let languages = send(class("NSLocale"), "preferredLanguages");
+        if languages.is_null() || send_usize(languages, "count") == 0 {
+            return None;
+        }
+        rust_string(send_index(languages, "objectAtIndex:", 0))
+    })
+}
+
+/// The user's preferred language as a BCP 47 tag such as "en-US", for the
+/// `{{language}}` template variable
+#[cfg(not(target_os = "macos"))]
+pub fn preferred_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| language_tag(&value))
+}
+
+/// "de_DE.UTF-8" or "de_DE@euro" to "de-DE"; `None` for the C/POSIX locale
+#[cfg(not(target_os = "macos"))]
+fn language_tag(posix_locale: &str) -> Option<String> {
+    let name = posix_locale
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default();
+    match name {
+        "" | "C" | "POSIX" => None,
+        _ => Some(name.replace('_', "-")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preferred_language_does_not_crash() {
+        if let Some(language) = preferred_language() {
+            assert!(!language.is_empty());
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn posix_locales_become_language_tags() {
+        assert_eq!(language_tag("de_DE.UTF-8").as_deref(), Some("de-DE"));
+        assert_eq!(language_tag("fr_FR@euro").as_deref(), Some("fr-FR"));
+        assert_eq!(language_tag("en").as_deref(), Some("en"));
+        assert_eq!(language_tag("C.UTF-8"), None);
+        assert_eq!(language_tag("POSIX"), None);
+    }
+}
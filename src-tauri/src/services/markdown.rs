@@ -0,0 +1,516 @@
+// Conversions between the HTML flavor of a rich-text selection and the
+// Markdown the LLM reads and writes. Covers what survives a rewrite in mail
+// and docs editors: paragraphs, headings, emphasis, links, lists and code.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Output {
+    Markdown,
+    Plain,
+}
+
+/// Convert copied HTML into Markdown for the prompt
+pub fn html_to_markdown(html: &str) -> String {
+    HtmlRenderer::new(Output::Markdown).render(html)
+}
+
+/// Convert HTML into plain text, keeping paragraph and list structure
+pub fn html_to_plain(html: &str) -> String {
+    HtmlRenderer::new(Output::Plain).render(html)
+}
+
+/// Plain-text flavor of an LLM Markdown answer
+pub fn markdown_to_plain(markdown: &str) -> String {
+    html_to_plain(&markdown_to_html(markdown))
+}
+
+struct HtmlRenderer {
+    mode: Output,
+    out: String,
+    /// `None` for bullet lists, `Some(next number)` for ordered lists
+    lists: Vec<Option<u32>>,
+    /// Open inline markers and where they were written, to drop empty pairs
+    inline: Vec<(&'static str, usize)>,
+    links: Vec<Option<String>>,
+    pre: usize,
+    skip: usize,
+}
+
+impl HtmlRenderer {
+    fn new(mode: Output) -> Self {
+        Self {
+            mode,
+            out: String::new(),
+            lists: Vec::new(),
+            inline: Vec::new(),
+            links: Vec::new(),
+            pre: 0,
+            skip: 0,
+        }
+    }
+
+    fn render(mut self, html: &str) -> String {
+        let mut rest = html;
+        while !rest.is_empty() {
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            } else if rest.starts_with('<') {
+                // An unterminated `<` is text, not the start of a tag
+                let Some(end) = rest.find('>') else {
+                    self.text(&decode_entities(rest));
+                    break;
+                };
+                self.tag(&rest[1..end]);
+                rest = &rest[end + 1..];
+            } else {
+                let end = rest.find('<').unwrap_or(rest.len());
+                self.text(&decode_entities(&rest[..end]));
+                rest = &rest[end..];
+            }
+        }
+        self.out.trim().to_string()
+    }
+
+    fn markdown(&self) -> bool {
+        self.mode == Output::Markdown
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    fn trim_trailing_spaces(&mut self) {
+        let trimmed = self.out.trim_end_matches([' ', '\t']).len();
+        self.out.truncate(trimmed);
+    }
+
+    fn line_break(&mut self) {
+        self.trim_trailing_spaces();
+        self.out.push('\n');
+    }
+
+    fn block_break(&mut self) {
+        self.trim_trailing_spaces();
+        if self.out.is_empty() || self.out.ends_with("\n\n") {
+            return;
+        }
+        self.out.push_str(if self.out.ends_with('\n') { "\n" } else { "\n\n" });
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.skip > 0 {
+            return;
+        }
+        if self.pre > 0 {
+            self.out.push_str(text);
+            return;
+        }
+        // Collapse whitespace runs like a browser would
+        if text.starts_with(char::is_whitespace) && !self.at_line_start() && !self.out.ends_with(' ') {
+            self.out.push(' ');
+        }
+        let mut words = text.split_whitespace().peekable();
+        while let Some(word) = words.next() {
+            self.out.push_str(word);
+            if words.peek().is_some() || text.ends_with(char::is_whitespace) {
+                self.out.push(' ');
+            }
+        }
+    }
+
+    fn open_inline(&mut self, marker: &'static str) {
+        if self.markdown() {
+            self.out.push_str(marker);
+            self.inline.push((marker, self.out.len()));
+        }
+    }
+
+    fn close_inline(&mut self, marker: &'static str) {
+        if !self.markdown() {
+            return;
+        }
+        let Some(pos) = self.inline.iter().rposition(|(m, _)| *m == marker) else {
+            return;
+        };
+        let (_, start) = self.inline.remove(pos);
+        if self.out.len() == start {
+            self.out.truncate(start - marker.len());
+            return;
+        }
+        // `**bold **` is not emphasis in Markdown; move the space outside
+        let trimmed = self.out.trim_end_matches(' ').len();
+        let spaces = self.out.len() - trimmed;
+        self.out.truncate(trimmed);
+        self.out.push_str(marker);
+        self.out.push_str(&" ".repeat(spaces));
+    }
+
+    fn tag(&mut self, raw: &str) {
+        let raw = raw.trim();
+        let closing = raw.starts_with('/');
+        let body = raw.trim_start_matches('/').trim_end_matches('/');
+        let name_end = body.find(|c: char| c.is_whitespace()).unwrap_or(body.len());
+        let name = body[..name_end].to_ascii_lowercase();
+        let attrs = &body[name_end..];
+
+        match (name.as_str(), closing) {
+            ("script" | "style" | "head" | "title", false) => self.skip += 1,
+            ("script" | "style" | "head" | "title", true) => self.skip = self.skip.saturating_sub(1),
+            ("br", _) => self.line_break(),
+            ("p" | "div" | "section" | "article" | "blockquote" | "table" | "tr", _) => {
+                self.block_break()
+            }
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", false) => {
+                self.block_break();
+                if self.markdown() {
+                    let level = name[1..].parse().unwrap_or(1);
+                    self.out.push_str(&"#".repeat(level));
+                    self.out.push(' ');
+                }
+            }
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", true) => self.block_break(),
+            ("b" | "strong", false) => self.open_inline("**"),
+            ("b" | "strong", true) => self.close_inline("**"),
+            ("i" | "em", false) => self.open_inline("*"),
+            ("i" | "em", true) => self.close_inline("*"),
+            ("code", false) if self.pre == 0 => self.open_inline("`"),
+            ("code", true) if self.pre == 0 => self.close_inline("`"),
+            ("pre", false) => {
+                self.block_break();
+                if self.markdown() {
+                    self.out.push_str("```\n");
+                }
+                self.pre += 1;
+            }
+            ("pre", true) => {
+                self.pre = self.pre.saturating_sub(1);
+                if !self.at_line_start() {
+                    self.out.push('\n');
+                }
+                if self.markdown() {
+                    self.out.push_str("```");
+                }
+                self.block_break();
+            }
+            ("a", false) => {
+                let href = attribute(attrs, "href");
+                if self.markdown() && href.is_some() {
+                    self.out.push('[');
+                }
+                self.links.push(href);
+            }
+            ("a", true) => {
+                if let Some(Some(href)) = self.links.pop() {
+                    if self.markdown() {
+                        self.trim_trailing_spaces();
+                        self.out.push_str(&format!("]({})", href));
+                    }
+                }
+            }
+            ("ul", false) | ("ol", false) => {
+                if self.lists.is_empty() {
+                    self.block_break();
+                } else if !self.at_line_start() {
+                    self.line_break();
+                }
+                let start = attribute(attrs, "start").and_then(|s| s.parse().ok());
+                self.lists.push((name == "ol").then(|| start.unwrap_or(1)));
+            }
+            ("ul", true) | ("ol", true) => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.block_break();
+                }
+            }
+            ("li", false) => {
+                if !self.at_line_start() {
+                    self.line_break();
+                }
+                let depth = self.lists.len().max(1);
+                self.out.push_str(&"  ".repeat(depth - 1));
+                match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        self.out.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => self.out.push_str("- "),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Value of `name="..."` (or single-quoted / bare) in a tag's attribute list
+fn attribute(attrs: &str, name: &str) -> Option<String> {
+    let lower = attrs.to_ascii_lowercase();
+    let mut search = 0;
+    while let Some(found) = lower[search..].find(name) {
+        let start = search + found;
+        search = start + name.len();
+        let boundary = start == 0 || lower[..start].ends_with(char::is_whitespace);
+        let rest = attrs[search..].trim_start();
+        if !boundary || !rest.starts_with('=') {
+            continue;
+        }
+        let value = rest[1..].trim_start();
+        let value = match value.chars().next() {
+            Some(q @ ('"' | '\'')) => value[1..].split(q).next().unwrap_or(""),
+            _ => value.split(char::is_whitespace).next().unwrap_or(""),
+        };
+        return Some(decode_entities(value));
+    }
+    None
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..].find(';').filter(|end| *end <= 10).and_then(|end| {
+            let entity = &rest[1..end + 1];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end + 2))
+        });
+        match decoded {
+            Some((c, consumed)) => {
+                out.push(c);
+                rest = &rest[consumed..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ListKind {
+    Bullet,
+    Ordered,
+}
+
+impl ListKind {
+    fn tag(self) -> &'static str {
+        match self {
+            Self::Bullet => "ul",
+            Self::Ordered => "ol",
+        }
+    }
+}
+
+/// Render the LLM's Markdown answer as HTML for the rich clipboard flavor
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut html = String::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut list: Option<ListKind> = None;
+    let mut code: Option<Vec<&str>> = None;
+
+    let flush = |html: &mut String, paragraph: &mut Vec<&str>, list: &mut Option<ListKind>| {
+        if !paragraph.is_empty() {
+            let lines: Vec<String> = paragraph.drain(..).map(|l| inline(&escape_html(l))).collect();
+            html.push_str(&format!("<p>{}</p>", lines.join("<br>")));
+        }
+        if let Some(kind) = list.take() {
+            html.push_str(&format!("</{}>", kind.tag()));
+        }
+    };
+
+    for line in markdown.lines() {
+        if let Some(lines) = code.as_mut() {
+            if line.trim_start().starts_with("```") {
+                html.push_str(&format!("<pre><code>{}</code></pre>", escape_html(&lines.join("\n"))));
+                code = None;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            flush(&mut html, &mut paragraph, &mut list);
+            code = Some(Vec::new());
+        } else if trimmed.is_empty() {
+            flush(&mut html, &mut paragraph, &mut list);
+        } else if let Some((level, text)) = heading(trimmed) {
+            flush(&mut html, &mut paragraph, &mut list);
+            html.push_str(&format!("<h{0}>{1}</h{0}>", level, inline(&escape_html(text))));
+        } else if let Some((kind, item)) = list_item(trimmed) {
+            if !paragraph.is_empty() || list != Some(kind) {
+                flush(&mut html, &mut paragraph, &mut list);
+                html.push_str(&format!("<{}>", kind.tag()));
+                list = Some(kind);
+            }
+            html.push_str(&format!("<li>{}</li>", inline(&escape_html(item))));
+        } else {
+            if list.is_some() {
+                flush(&mut html, &mut paragraph, &mut list);
+            }
+            paragraph.push(trimmed);
+        }
+    }
+    if let Some(lines) = code {
+        html.push_str(&format!("<pre><code>{}</code></pre>", escape_html(&lines.join("\n"))));
+    }
+    flush(&mut html, &mut paragraph, &mut list);
+    html
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (1..=6).contains(&level).then_some((level, text.trim()))
+}
+
+fn list_item(line: &str) -> Option<(ListKind, &str)> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = line.strip_prefix(bullet) {
+            return Some((ListKind::Bullet, item));
+        }
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        if let Some(item) = line[digits..].strip_prefix(". ") {
+            return Some((ListKind::Ordered, item));
+        }
+    }
+    None
+}
+
+/// Inline Markdown (code, strong, emphasis, links) on already-escaped text
+fn inline(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut prev = ' ';
+    while let Some(c) = rest.chars().next() {
+        // `snake_case` is not emphasis
+        if c != '_' || !prev.is_alphanumeric() {
+            if let Some((html, consumed)) = inline_span(rest) {
+                out.push_str(&html);
+                rest = &rest[consumed..];
+                prev = ' ';
+                continue;
+            }
+        }
+        out.push(c);
+        prev = c;
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+fn inline_span(s: &str) -> Option<(String, usize)> {
+    if let Some(inner) = s.strip_prefix('`') {
+        let end = inner.find('`')?;
+        return Some((format!("<code>{}</code>", &inner[..end]), end + 2));
+    }
+    for (marker, tag) in [("**", "strong"), ("__", "strong"), ("*", "em"), ("_", "em")] {
+        let Some(inner) = s.strip_prefix(marker) else {
+            continue;
+        };
+        if inner.starts_with(char::is_whitespace) {
+            continue;
+        }
+        match inner.find(marker) {
+            Some(end) if end > 0 && !inner[..end].ends_with(char::is_whitespace) => {
+                let html = format!("<{0}>{1}</{0}>", tag, inline(&inner[..end]));
+                return Some((html, end + marker.len() * 2));
+            }
+            _ => continue,
+        }
+    }
+    if let Some(inner) = s.strip_prefix('[') {
+        let close = inner.find("](")?;
+        let url_len = inner[close + 2..].find(')')?;
+        let url = &inner[close + 2..close + 2 + url_len];
+        let html = format!("<a href=\"{}\">{}</a>", url, inline(&inner[..close]));
+        return Some((html, close + url_len + 4));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_to_markdown_keeps_formatting() {
+        let html = "<html><head><style>p{color:red}</style></head><body>\
+            <p>Hello <b>bold </b>and <a href=\"https://example.com/?a=1&amp;b=2\">a link</a>.</p>\
+            <ul><li>one</li><li><i>two</i></li></ul>\
+            <ol start=\"3\"><li>three</li></ol></body></html>";
+        assert_eq!(
+            html_to_markdown(html),
+            "Hello **bold** and [a link](https://example.com/?a=1&b=2).\n\n- one\n- *two*\n\n3. three"
+        );
+    }
+
+    #[test]
+    fn html_to_markdown_handles_breaks_headings_and_entities() {
+        let html = "<h2>Title</h2><div>line&nbsp;one<br>line &lt;two&gt; &#8212; &#x2713;</div>";
+        assert_eq!(html_to_markdown(html), "## Title\n\nline one\nline <two> — ✓");
+    }
+
+    #[test]
+    fn html_to_plain_drops_markup() {
+        let html = "<p><strong>Hi</strong> <a href=\"x\">there</a></p><ul><li>a</li></ul>";
+        assert_eq!(html_to_plain(html), "Hi there\n\n- a");
+    }
+
+    #[test]
+    fn unterminated_tag_is_kept_as_text() {
+        assert_eq!(html_to_markdown("<b>x</b> <"), "**x** <");
+        assert_eq!(html_to_plain("x <é"), "x <é");
+    }
+
+    #[test]
+    fn markdown_to_html_renders_blocks_and_inline() {
+        let md = "# Title\n\nSome **bold**, *em*, `code` and [link](https://x.y/?a=1&b=2).\nNext line\n\n- a\n- b\n\n1. first\n\n```\n<tag>\n```";
+        assert_eq!(
+            markdown_to_html(md),
+            "<h1>Title</h1>\
+             <p>Some <strong>bold</strong>, <em>em</em>, <code>code</code> and \
+             <a href=\"https://x.y/?a=1&amp;b=2\">link</a>.<br>Next line</p>\
+             <ul><li>a</li><li>b</li></ul>\
+             <ol><li>first</li></ol>\
+             <pre><code>&lt;tag&gt;</code></pre>"
+        );
+    }
+
+    #[test]
+    fn markdown_leaves_snake_case_and_lone_markers() {
+        assert_eq!(markdown_to_html("a snake_case_name * 2"), "<p>a snake_case_name * 2</p>");
+    }
+
+    #[test]
+    fn markdown_roundtrips_through_html() {
+        let md = "Hello **bold** and [a link](https://example.com).\n\n- one\n- *two*";
+        assert_eq!(html_to_markdown(&markdown_to_html(md)), md);
+        assert_eq!(markdown_to_plain(md), "Hello bold and a link.\n\n- one\n- two");
+    }
+}
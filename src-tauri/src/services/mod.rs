@@ -1,4 +1,26 @@
+#[cfg(target_os = "macos")]
 pub mod accessibility;
+pub mod action_inputs;
+pub mod app_rules;
+#[cfg(target_os = "macos")]
+pub mod cf;
 pub mod clipboard;
+pub mod history_export;
+pub mod history_import;
+pub mod history_store;
 pub mod key_storage;
+pub mod locale;
+pub mod markdown;
+#[cfg(target_os = "macos")]
+pub mod objc;
+#[cfg(target_os = "macos")]
+pub mod pasteboard;
 pub mod response_normalizer;
+pub mod rewrite_pipeline;
+pub mod selection;
+pub mod template;
+pub mod text_backend;
+pub mod text_diff;
+pub mod undo_stack;
+#[cfg(target_os = "macos")]
+pub mod workspace;
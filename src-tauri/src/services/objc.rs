@@ -0,0 +1,108 @@
+// Minimal Objective-C runtime access for the AppKit calls we need, without
+// pulling in an objc crate. Shared by the pasteboard and workspace modules.
+use std::ffi::{c_char, c_void, CStr, CString};
+
+pub type Id = *mut c_void;
+pub type Sel = *const c_void;
+pub type NSUInteger = usize;
+
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {}
+
+#[link(name = "objc")]
+extern "C" {
+    fn objc_getClass(name: *const c_char) -> Id;
+    fn sel_registerName(name: *const c_char) -> Sel;
+    pub fn objc_msgSend();
+    fn objc_autoreleasePoolPush() -> *mut c_void;
+    fn objc_autoreleasePoolPop(pool: *mut c_void);
+}
+
+pub fn class(name: &str) -> Id {
+    let name = CString::new(name).unwrap();
+    unsafe { objc_getClass(name.as_ptr()) }
+}
+
+pub fn sel(name: &str) -> Sel {
+    let name = CString::new(name).unwrap();
+    unsafe { sel_registerName(name.as_ptr()) }
+}
+
+// objc_msgSend has to be cast to the exact signature of each call site
+pub unsafe fn send(receiver: Id, selector: &str) -> Id {
+    let f: extern "C" fn(Id, Sel) -> Id = std::mem::transmute(objc_msgSend as *const c_void);
+    f(receiver, sel(selector))
+}
+
+pub unsafe fn send_usize(receiver: Id, selector: &str) -> NSUInteger {
+    let f: extern "C" fn(Id, Sel) -> NSUInteger =
+        std::mem::transmute(objc_msgSend as *const c_void);
+    f(receiver, sel(selector))
+}
+
+pub unsafe fn send_i32(receiver: Id, selector: &str) -> i32 {
+    let f: extern "C" fn(Id, Sel) -> i32 = std::mem::transmute(objc_msgSend as *const c_void);
+    f(receiver, sel(selector))
+}
+
+pub unsafe fn send_id(receiver: Id, selector: &str, arg: Id) -> Id {
+    let f: extern "C" fn(Id, Sel, Id) -> Id = std::mem::transmute(objc_msgSend as *const c_void);
+    f(receiver, sel(selector), arg)
+}
+
+pub unsafe fn send_index(receiver: Id, selector: &str, index: NSUInteger) -> Id {
+    let f: extern "C" fn(Id, Sel, NSUInteger) -> Id =
+        std::mem::transmute(objc_msgSend as *const c_void);
+    f(receiver, sel(selector), index)
+}
+
+pub unsafe fn send_id_id(receiver: Id, selector: &str, a: Id, b: Id) -> Id {
+    let f: extern "C" fn(Id, Sel, Id, Id) -> Id = std::mem::transmute(objc_msgSend as *const c_void);
+    f(receiver, sel(selector), a, b)
+}
+
+pub unsafe fn ns_string(s: &str) -> Id {
+    let c_str = CString::new(s).unwrap_or_default();
+    let f: extern "C" fn(Id, Sel, *const c_char) -> Id =
+        std::mem::transmute(objc_msgSend as *const c_void);
+    f(class("NSString"), sel("stringWithUTF8String:"), c_str.as_ptr())
+}
+
+pub unsafe fn rust_string(ns_str: Id) -> Option<String> {
+    let f: extern "C" fn(Id, Sel) -> *const c_char =
+        std::mem::transmute(objc_msgSend as *const c_void);
+    let ptr = f(ns_str, sel("UTF8String"));
+    (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_string_lossy().into_owned())
+}
+
+pub unsafe fn ns_data(bytes: &[u8]) -> Id {
+    let f: extern "C" fn(Id, Sel, *const c_void, NSUInteger) -> Id =
+        std::mem::transmute(objc_msgSend as *const c_void);
+    f(
+        class("NSData"),
+        sel("dataWithBytes:length:"),
+        bytes.as_ptr() as *const c_void,
+        bytes.len(),
+    )
+}
+
+pub unsafe fn rust_bytes(data: Id) -> Vec<u8> {
+    let f: extern "C" fn(Id, Sel) -> *const u8 = std::mem::transmute(objc_msgSend as *const c_void);
+    let ptr = f(data, sel("bytes"));
+    let len = send_usize(data, "length");
+    if ptr.is_null() || len == 0 {
+        Vec::new()
+    } else {
+        std::slice::from_raw_parts(ptr, len).to_vec()
+    }
+}
+
+/// Run `f` inside an autorelease pool; we are called from non-AppKit threads
+pub fn with_pool<T>(f: impl FnOnce() -> T) -> T {
+    unsafe {
+        let pool = objc_autoreleasePoolPush();
+        let result = f();
+        objc_autoreleasePoolPop(pool);
+        result
+    }
+}
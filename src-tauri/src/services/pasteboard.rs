@@ -0,0 +1,213 @@
+use crate::services::objc::{
+    class, ns_data, ns_string, objc_msgSend, rust_bytes, rust_string, sel, send, send_id,
+    send_id_id, send_index, send_usize, with_pool, Id, NSUInteger, Sel,
+};
+use crate::services::text_backend::ClipboardSnapshot;
+use std::ffi::c_void;
+
+const HTML_TYPE: &str = "public.html";
+const RTF_TYPE: &str = "public.rtf";
+// Values of NSDocumentTypeDocumentAttribute and NSHTMLTextDocumentType
+const DOCUMENT_TYPE_ATTRIBUTE: &str = "DocumentType";
+const HTML_DOCUMENT_TYPE: &str = "NSHTML";
+
+#[repr(C)]
+struct NSRange {
+    location: NSUInteger,
+    length: NSUInteger,
+}
+
+unsafe fn general_pasteboard() -> Id {
+    send(class("NSPasteboard"), "generalPasteboard")
+}
+
+/// The general pasteboard's change counter; it increases on every write
+pub fn change_count() -> isize {
+    with_pool(|| unsafe { send_usize(general_pasteboard(), "changeCount") as isize })
+}
+
+/// Copy every item and type currently on the general pasteboard, so images,
+/// RTF and file references survive a simulated copy or paste
+pub fn snapshot() -> ClipboardSnapshot {
+    with_pool(|| unsafe { snapshot_of(general_pasteboard()) })
+}
+
+/// HTML flavor of the current clipboard; RTF-only content (e.g. from Pages
+/// or TextEdit) is converted to HTML through NSAttributedString
+pub fn read_html() -> Option<String> {
+    with_pool(|| unsafe { html_of(general_pasteboard()) })
+}
+
+/// Replace the general pasteboard contents with a previous snapshot
+pub fn restore(snapshot: &ClipboardSnapshot) -> Result<(), String> {
+    with_pool(|| unsafe { restore_to(general_pasteboard(), snapshot) })
+}
+
+unsafe fn snapshot_of(pasteboard: Id) -> ClipboardSnapshot {
+    let items = send(pasteboard, "pasteboardItems");
+    if items.is_null() {
+        return ClipboardSnapshot::default();
+    }
+
+    let mut snapshot = ClipboardSnapshot::default();
+    for i in 0..send_usize(items, "count") {
+        let item = send_index(items, "objectAtIndex:", i);
+        let types = send(item, "types");
+        let mut flavors = Vec::new();
+        for j in 0..send_usize(types, "count") {
+            let pb_type = send_index(types, "objectAtIndex:", j);
+            let data = send_id(item, "dataForType:", pb_type);
+            if data.is_null() {
+                continue;
+            }
+            if let Some(name) = rust_string(pb_type) {
+                flavors.push((name, rust_bytes(data)));
+            }
+        }
+        if !flavors.is_empty() {
+            snapshot.items.push(flavors);
+        }
+    }
+    snapshot
+}
+
+unsafe fn html_of(pasteboard: Id) -> Option<String> {
+    let html = send_id(pasteboard, "dataForType:", ns_string(HTML_TYPE));
+    if !html.is_null() {
+        return Some(String::from_utf8_lossy(&rust_bytes(html)).into_owned());
+    }
+    let rtf = send_id(pasteboard, "dataForType:", ns_string(RTF_TYPE));
+    if rtf.is_null() {
+        return None;
+    }
+    rtf_to_html(rtf)
+}
+
+unsafe fn rtf_to_html(rtf: Id) -> Option<String> {
+    let init: extern "C" fn(Id, Sel, Id, *mut Id) -> Id =
+        std::mem::transmute(objc_msgSend as *const c_void);
+    let attributed = init(
+        send(class("NSAttributedString"), "alloc"),
+        sel("initWithRTF:documentAttributes:"),
+        rtf,
+        std::ptr::null_mut(),
+    );
+    if attributed.is_null() {
+        return None;
+    }
+
+    let attributes = send_id_id(
+        class("NSDictionary"),
+        "dictionaryWithObject:forKey:",
+        ns_string(HTML_DOCUMENT_TYPE),
+        ns_string(DOCUMENT_TYPE_ATTRIBUTE),
+    );
+    let range = NSRange {
+        location: 0,
+        length: send_usize(attributed, "length"),
+    };
+    let export: extern "C" fn(Id, Sel, NSRange, Id, *mut Id) -> Id =
+        std::mem::transmute(objc_msgSend as *const c_void);
+    let html = export(
+        attributed,
+        sel("dataFromRange:documentAttributes:error:"),
+        range,
+        attributes,
+        std::ptr::null_mut(),
+    );
+    let result = (!html.is_null()).then(|| String::from_utf8_lossy(&rust_bytes(html)).into_owned());
+    send(attributed, "release");
+    result
+}
+
+unsafe fn restore_to(pasteboard: Id, snapshot: &ClipboardSnapshot) -> Result<(), String> {
+    send_usize(pasteboard, "clearContents");
+    if snapshot.is_empty() {
+        return Ok(());
+    }
+
+    let objects = send(class("NSMutableArray"), "array");
+    for flavors in &snapshot.items {
+        let item = send(send(class("NSPasteboardItem"), "alloc"), "init");
+        for (name, bytes) in flavors {
+            send_id_id(item, "setData:forType:", ns_data(bytes), ns_string(name));
+        }
+        send_id(objects, "addObject:", item);
+        send(item, "release");
+    }
+
+    let f: extern "C" fn(Id, Sel, Id) -> bool =
+        std::mem::transmute(objc_msgSend as *const c_void);
+    if f(pasteboard, sel("writeObjects:"), objects) {
+        Ok(())
+    } else {
+        Err("Failed to restore clipboard contents".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ns_string_roundtrip() {
+        with_pool(|| unsafe {
+            let s = ns_string("public.utf8-plain-text ✓");
+            assert_eq!(rust_string(s).as_deref(), Some("public.utf8-plain-text ✓"));
+        });
+    }
+
+    #[test]
+    fn ns_data_roundtrip() {
+        with_pool(|| unsafe {
+            let bytes = [0u8, 159, 146, 150, 255];
+            assert_eq!(rust_bytes(ns_data(&bytes)), bytes.to_vec());
+        });
+    }
+
+    /// Run `f` on a pasteboard of its own, leaving the user's clipboard alone
+    fn with_private_pasteboard<T>(f: impl FnOnce(Id) -> T) -> T {
+        with_pool(|| unsafe {
+            let pasteboard = send(class("NSPasteboard"), "pasteboardWithUniqueName");
+            let result = f(pasteboard);
+            send(pasteboard, "releaseGlobally");
+            result
+        })
+    }
+
+    #[test]
+    fn html_converts_rtf_flavor() {
+        let html = with_private_pasteboard(|pasteboard| unsafe {
+            let rtf = ClipboardSnapshot {
+                items: vec![vec![(RTF_TYPE.into(), b"{\\rtf1\\ansi {\\b bold} text}".to_vec())]],
+            };
+            restore_to(pasteboard, &rtf).unwrap();
+            html_of(pasteboard).unwrap_or_default()
+        });
+        assert!(html.contains("bold"), "unexpected html: {}", html);
+    }
+
+    #[test]
+    fn restore_bumps_change_count() {
+        with_private_pasteboard(|pasteboard| unsafe {
+            let before = send_usize(pasteboard, "changeCount");
+            restore_to(pasteboard, &ClipboardSnapshot::default()).unwrap();
+            assert!(send_usize(pasteboard, "changeCount") > before);
+        });
+    }
+
+    #[test]
+    fn snapshot_restore_keeps_all_flavors() {
+        let sample = ClipboardSnapshot {
+            items: vec![vec![
+                ("public.utf8-plain-text".into(), b"hello".to_vec()),
+                ("public.rtf".into(), b"{\\rtf1 hello}".to_vec()),
+            ]],
+        };
+        let restored = with_private_pasteboard(|pasteboard| unsafe {
+            restore_to(pasteboard, &sample).unwrap();
+            snapshot_of(pasteboard)
+        });
+        assert_eq!(restored, sample);
+    }
+}
@@ -0,0 +1,1049 @@
+use crate::commands::llm_provider::{call_llm, call_llm_candidates};
+use crate::models::{
+    AppContext, FallbackScope, LLMResponse, ProviderConfig, RewriteAction, SelectionContext,
+    StepOutput, TemplateVariable, TextInteractionError,
+};
+use crate::services::template::Template;
+use crate::services::text_backend::TextInteractionBackend;
+use crate::services::{action_inputs, app_rules, locale, selection};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const MARKDOWN_RULE: &str = "The text is formatted as Markdown. Keep its formatting (bold, italics, links, lists, headings) in Markdown in your output.";
+/// Characters of surrounding text per side when the action doesn't set a limit
+const DEFAULT_CONTEXT_CHARS: usize = 500;
+/// Cap on `context_chars` so a long document can't swamp the prompt
+const MAX_CONTEXT_CHARS: usize = 4000;
+/// Cap on alternatives per run; each may cost a full request
+pub const MAX_CANDIDATES: usize = 5;
+
+/// A selection rewritten by the LLM, not yet pasted back
+#[derive(Debug, Clone)]
+pub struct Rewrite {
+    /// Plain text of the selection, as recorded for undo
+    pub original_text: String,
+    /// Text sent to the LLM: the selection's Markdown when formatting is kept
+    pub input_text: String,
+    /// `response.text` is Markdown and is pasted as rich text
+    pub markdown: bool,
+    /// Application the selection was read from
+    pub app: Option<AppContext>,
+    /// The final text, with duration and tokens summed over all steps
+    pub response: LLMResponse,
+    /// Every step's output for multi-step actions; empty otherwise
+    pub steps: Vec<StepOutput>,
+    /// Alternatives to pick from, `response.text` first, when the action
+    /// asks for several; empty otherwise
+    pub candidates: Vec<String>,
+}
+
+/// One LLM call of an action run: the prompts to send and where to send them
+#[derive(Debug, Clone)]
+pub struct RewriteStep {
+    pub name: String,
+    /// Prompt fields for the step; its per-app overrides still apply
+    pub prompts: RewriteAction,
+    pub provider: ProviderConfig,
+    pub api_key: String,
+}
+
+/// Settings for an action run besides the action itself
+#[derive(Debug, Clone, Default)]
+pub struct RewriteSettings {
+    /// Apps whose selection is never read
+    pub secure_apps: Vec<String>,
+    /// User-defined template variables
+    pub variables: Vec<TemplateVariable>,
+    /// Values entered for the action's inputs; missing ones use their default
+    pub inputs: HashMap<String, String>,
+}
+
+/// The LLM calls for running `action`: its own prompts with `provider`, or
+/// its `steps`, looking up referenced actions in `actions` and step providers
+/// in `providers`. `api_key` returns the saved key for a provider.
+pub fn resolve_steps(
+    action: &RewriteAction,
+    actions: &[RewriteAction],
+    providers: &[ProviderConfig],
+    provider: &ProviderConfig,
+    api_key: impl Fn(&ProviderConfig) -> Option<String>,
+) -> Result<Vec<RewriteStep>, String> {
+    if action.steps.is_empty() {
+        return Ok(vec![RewriteStep {
+            name: action.name.clone(),
+            prompts: action.clone(),
+            provider: provider.clone(),
+            api_key: api_key(provider).ok_or("No API key configured. Go to Provider tab.")?,
+        }]);
+    }
+
+    let mut steps = Vec::with_capacity(action.steps.len());
+    for (i, step) in action.steps.iter().enumerate() {
+        let fail = |problem: &str| format!("Step {} of '{}' {}", i + 1, action.name, problem);
+        let (default_name, prompts) = match &step.action_id {
+            Some(id) => {
+                let other = actions
+                    .iter()
+                    .find(|a| &a.id == id)
+                    .ok_or_else(|| fail("uses an action that no longer exists"))?;
+                if !other.steps.is_empty() {
+                    return Err(fail(&format!(
+                        "can't run '{}', which has steps of its own",
+                        other.name
+                    )));
+                }
+                (other.name.clone(), other.clone())
+            }
+            None => (
+                format!("Step {}", i + 1),
+                RewriteAction {
+                    system_prompt: step.system_prompt.clone(),
+                    user_template: step.user_template.clone(),
+                    output_rules: step.output_rules.clone(),
+                    app_overrides: Vec::new(),
+                    steps: Vec::new(),
+                    examples: Vec::new(),
+                    ..action.clone()
+                },
+            ),
+        };
+
+        let mut step_provider = match &step.provider_id {
+            Some(id) => providers
+                .iter()
+                .find(|p| &p.id == id)
+                .cloned()
+                .ok_or_else(|| fail("uses a provider that no longer exists"))?,
+            None => provider.clone(),
+        };
+        if let Some(model) = step.model.as_deref().map(str::trim).filter(|m| !m.is_empty()) {
+            step_provider.model = model.to_string();
+        }
+        let key = api_key(&step_provider).ok_or_else(|| {
+            format!("No API key configured for {}. Go to Provider tab.", step_provider.name)
+        })?;
+
+        steps.push(RewriteStep {
+            name: if step.name.trim().is_empty() {
+                default_name
+            } else {
+                step.name.clone()
+            },
+            prompts,
+            provider: step_provider,
+            api_key: key,
+        });
+    }
+    Ok(steps)
+}
+
+/// System prompt and rendered user template for running `action` with
+/// `values` for the template's variables
+pub fn build_prompts(
+    action: &RewriteAction,
+    markdown: bool,
+    values: &HashMap<String, String>,
+) -> Result<(String, String), String> {
+    let mut system_prompt = action.system_prompt.clone();
+    if !action.output_rules.is_empty() {
+        system_prompt.push_str("\n\n");
+        system_prompt.push_str(&action.output_rules);
+    }
+    if markdown {
+        system_prompt.push_str("\n\n");
+        system_prompt.push_str(MARKDOWN_RULE);
+    }
+    let user_prompt = Template::parse(&action.user_template)?.render(values)?;
+    Ok((system_prompt, user_prompt))
+}
+
+/// `examples` with each input rendered through `template` in place of the
+/// real text, so the example turns have the same shape as the real one
+fn render_examples(
+    template: &Template,
+    examples: &[(String, String)],
+    values: &HashMap<String, String>,
+) -> Result<Vec<(String, String)>, String> {
+    let mut values = values.clone();
+    examples
+        .iter()
+        .map(|(input, output)| {
+            values.insert("text".to_string(), input.clone());
+            Ok((template.render(&values)?, output.clone()))
+        })
+        .collect()
+}
+
+/// Read the selection from the focused app and run `steps` on it, each
+/// rewriting the previous output. `action` decides where it may run and how
+/// the selection is read; per-app prompt overrides apply to every step.
+pub async fn rewrite_selection(
+    backend: &dyn TextInteractionBackend,
+    action: &RewriteAction,
+    steps: &[RewriteStep],
+    settings: &RewriteSettings,
+) -> Result<Rewrite, String> {
+    // Resolve per-app rules before touching the selection or the clipboard
+    let app = backend.frontmost_app();
+    app_rules::check_secure_app(&settings.secure_apps, app.as_ref())?;
+    app_rules::check_allowed(action, app.as_ref())?;
+    let steps: Vec<(&RewriteStep, RewriteAction)> = steps
+        .iter()
+        .map(|step| (step, app_rules::resolve(&step.prompts, app.as_ref())))
+        .collect();
+    let mut templates = Vec::with_capacity(steps.len());
+    for (step, prompts) in &steps {
+        let template = Template::parse(&prompts.user_template)
+            .map_err(|e| format!("Invalid template in '{}': {}", step.name, e))?;
+        templates.push(template);
+    }
+    let uses = |name: &str| templates.iter().any(|t| t.uses(name));
+    let inputs = action_inputs::resolve_values(&action.inputs, &settings.inputs)?;
+
+    // Read the user's clipboard before copying the selection goes through it
+    let clipboard = if uses("clipboard") {
+        backend.read_clipboard_text().unwrap_or_default()
+    } else {
+        String::new()
+    };
+
+    let no_selection = TextInteractionError::NoSelection.to_string();
+    let (original_text, markdown) = match read_input(backend, action) {
+        // Widen an empty selection to the action's fallback scope and retry
+        Err(e) if e == no_selection && action.fallback_scope != FallbackScope::None => {
+            selection::select_fallback_scope(backend, action.fallback_scope)?;
+            read_input(backend, action)?
+        }
+        result => result?,
+    };
+
+    // Surrounding text is only read when a template asks for it
+    let context = if uses("context_before") || uses("context_after") {
+        let max_chars = action
+            .context_chars
+            .unwrap_or(DEFAULT_CONTEXT_CHARS)
+            .min(MAX_CONTEXT_CHARS);
+        backend.selection_context(max_chars).unwrap_or_default()
+    } else {
+        SelectionContext::default()
+    };
+
+    let is_markdown = markdown.is_some();
+    let input_text = markdown.unwrap_or_else(|| original_text.clone());
+    let mut values: HashMap<String, String> = settings
+        .variables
+        .iter()
+        .map(|v| (v.name.clone(), v.value.clone()))
+        .collect();
+    values.extend(inputs);
+    let builtins = [
+        ("app_name", app.as_ref().map(|a| a.name.clone()).unwrap_or_default()),
+        ("date", chrono::Local::now().format("%Y-%m-%d").to_string()),
+        ("clipboard", clipboard),
+        ("language", locale::preferred_language().unwrap_or_default()),
+        ("context_before", context.before),
+        ("context_after", context.after),
+    ];
+    values.extend(builtins.map(|(name, value)| (name.to_string(), value)));
+
+    let chained = !action.steps.is_empty();
+    let candidate_count = action.candidates.unwrap_or(1).clamp(1, MAX_CANDIDATES);
+    let mut response = LLMResponse {
+        text: input_text.clone(),
+        tokens_used: None,
+        duration_ms: 0,
+    };
+    let mut outputs = Vec::new();
+    let mut candidates = Vec::new();
+    for (i, (step, prompts)) in steps.iter().enumerate() {
+        // Each step rewrites the previous step's output
+        values.insert("text".to_string(), response.text.clone());
+        let (system_prompt, user_prompt) = build_prompts(prompts, is_markdown, &values)?;
+        let examples = render_examples(&templates[i], &prompts.examples, &values)?;
+        let (base_url, api_key, model) = (
+            step.provider.base_url.clone(),
+            step.api_key.clone(),
+            step.provider.model.clone(),
+        );
+        let timeout_secs = step.provider.timeout_secs;
+        // Alternatives are only generated for the final text
+        let step_response = if candidate_count > 1 && i == steps.len() - 1 {
+            call_llm_candidates(
+                base_url,
+                api_key,
+                model,
+                system_prompt,
+                examples,
+                user_prompt,
+                timeout_secs,
+                candidate_count,
+            )
+            .await
+            .map(|result| {
+                candidates = result.texts;
+                LLMResponse {
+                    text: candidates.first().cloned().unwrap_or_default(),
+                    tokens_used: result.tokens_used,
+                    duration_ms: result.duration_ms,
+                }
+            })
+        } else {
+            call_llm(
+                base_url,
+                api_key,
+                model,
+                system_prompt,
+                examples,
+                user_prompt,
+                timeout_secs,
+            )
+            .await
+        }
+        .map_err(|e| if chained { format!("{} failed: {}", step.name, e) } else { e })?;
+
+        if chained {
+            outputs.push(StepOutput {
+                name: step.name.clone(),
+                provider: step.provider.name.clone(),
+                model: step.provider.model.clone(),
+                text: step_response.text.clone(),
+                duration_ms: step_response.duration_ms,
+                tokens_used: step_response.tokens_used,
+            });
+        }
+        response = LLMResponse {
+            text: step_response.text,
+            tokens_used: match (response.tokens_used, step_response.tokens_used) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, b) => a.or(b),
+            },
+            duration_ms: response.duration_ms + step_response.duration_ms,
+        };
+    }
+
+    Ok(Rewrite {
+        original_text,
+        input_text,
+        markdown: is_markdown,
+        app,
+        response,
+        steps: outputs,
+        candidates,
+    })
+}
+
+/// The selection's plain text, plus its Markdown when the action keeps
+/// formatting
+fn read_input(
+    backend: &dyn TextInteractionBackend,
+    action: &RewriteAction,
+) -> Result<(String, Option<String>), String> {
+    // Rich selections are sent to the LLM as Markdown and pasted back as HTML
+    let (text, markdown) = if action.preserve_formatting {
+        let selected = selection::read_rich_selection(backend)?;
+        (selected.text, selected.markdown)
+    } else {
+        (selection::read_selected_text(backend)?, None)
+    };
+    if text.trim().is_empty() {
+        return Err(TextInteractionError::NoSelection.to_string());
+    }
+    Ok((text, markdown))
+}
+
+/// Paste the rewrite over the selection; returns the plain text that was pasted
+pub fn apply_rewrite(
+    backend: &dyn TextInteractionBackend,
+    rewrite: &Rewrite,
+    restore_delay: Duration,
+) -> Result<String, String> {
+    if rewrite.markdown {
+        selection::paste_markdown(backend, &rewrite.response.text, restore_delay)
+    } else {
+        selection::replace_text(backend, &rewrite.response.text, restore_delay)?;
+        Ok(rewrite.response.text.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ActionInput, ActionInputKind, ActionStep, AppPromptOverride, ReplaceMode};
+    use crate::services::text_backend::fake::FakeBackend;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    /// Serve one chat completion answering `reply`; resolves to the request body
+    async fn mock_llm(reply: &str) -> (String, JoinHandle<serde_json::Value>) {
+        let body = serde_json::json!({
+            "choices": [{ "message": { "role": "assistant", "content": reply } }],
+            "usage": { "total_tokens": 42 }
+        });
+        let (base_url, server) = mock_llm_with(1, move |_| (200, body.clone())).await;
+        let server = tokio::spawn(async move { server.await.unwrap().remove(0) });
+        (base_url, server)
+    }
+
+    /// Serve `count` requests one after another, answering each request body
+    /// with `respond`'s status and JSON; yields the request bodies
+    async fn mock_llm_with(
+        count: usize,
+        respond: impl Fn(&serde_json::Value) -> (u16, serde_json::Value) + Send + 'static,
+    ) -> (String, JoinHandle<Vec<serde_json::Value>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for _ in 0..count {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                let (header_end, content_length) = loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(pos) = text.find("\r\n\r\n") {
+                        let length = text[..pos]
+                            .lines()
+                            .filter_map(|l| l.split_once(':'))
+                            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                            .unwrap_or(0);
+                        break (pos + 4, length);
+                    }
+                };
+                while request.len() < header_end + content_length {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+
+                let body: serde_json::Value = serde_json::from_slice(&request[header_end..]).unwrap();
+                let (status, reply) = respond(&body);
+                let reply = reply.to_string();
+                let response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    reply.len(),
+                    reply
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+                requests.push(body);
+            }
+            requests
+        });
+
+        (base_url, server)
+    }
+
+    fn action(preserve_formatting: bool) -> RewriteAction {
+        RewriteAction {
+            id: "fix".into(),
+            name: "Fix Grammar".into(),
+            hotkey: "CmdOrCtrl+Shift+G".into(),
+            system_prompt: "Fix grammar.".into(),
+            user_template: "Rewrite: {{text}}".into(),
+            output_rules: "Output only the text.".into(),
+            enabled: true,
+            replace_mode: ReplaceMode::Immediate,
+            preserve_formatting,
+            include_apps: Vec::new(),
+            exclude_apps: Vec::new(),
+            app_overrides: Vec::new(),
+            context_chars: None,
+            fallback_scope: FallbackScope::None,
+            inputs: Vec::new(),
+            steps: Vec::new(),
+            candidates: None,
+            examples: Vec::new(),
+        }
+    }
+
+    /// Run `action` with `provider` and a test key, as `run_action` would
+    async fn run(
+        backend: &FakeBackend,
+        action: &RewriteAction,
+        provider: &ProviderConfig,
+        settings: &RewriteSettings,
+    ) -> Result<Rewrite, String> {
+        let steps = resolve_steps(action, &[], &[], provider, |_| Some("sk-test".into()))?;
+        rewrite_selection(backend, action, &steps, settings).await
+    }
+
+    fn provider(base_url: String) -> ProviderConfig {
+        ProviderConfig {
+            id: "mock".into(),
+            name: "mock".into(),
+            base_url,
+            model: "mock-model".into(),
+            timeout_secs: 5,
+        }
+    }
+
+    #[tokio::test]
+    async fn rewrites_selection_and_restores_clipboard() {
+        let backend = FakeBackend::new("Note: teh quick fox.", 6..19)
+            .with_app("Mail", "com.apple.mail")
+            .with_clipboard_text("user clipboard");
+        let (base_url, server) = mock_llm("the quick fox").await;
+
+        let rewrite = run(&backend, &action(false), &provider(base_url), &RewriteSettings::default())
+        .await
+        .unwrap();
+        let pasted = apply_rewrite(&backend, &rewrite, Duration::ZERO).unwrap();
+
+        let request = server.await.unwrap();
+        assert_eq!(request["model"], "mock-model");
+        assert_eq!(request["messages"][0]["content"], "Fix grammar.\n\nOutput only the text.");
+        assert_eq!(request["messages"][1]["content"], "Rewrite: teh quick fox");
+
+        assert_eq!(rewrite.original_text, "teh quick fox");
+        assert_eq!(rewrite.response.tokens_used, Some(42));
+        let app = rewrite.app.unwrap();
+        assert_eq!(app.name, "Mail");
+        assert_eq!(app.bundle_id.as_deref(), Some("com.apple.mail"));
+        assert_eq!(pasted, "the quick fox");
+        assert_eq!(backend.value(), "Note: the quick fox.");
+        assert_eq!(backend.read_clipboard_text().as_deref(), Some("user clipboard"));
+    }
+
+    #[tokio::test]
+    async fn direct_write_leaves_clipboard_alone() {
+        let backend = FakeBackend::new("Note: teh quick fox.", 6..19)
+            .with_direct_write()
+            .with_clipboard_text("user clipboard");
+        let (base_url, server) = mock_llm("the quick fox").await;
+
+        let rewrite = run(&backend, &action(false), &provider(base_url), &RewriteSettings::default())
+        .await
+        .unwrap();
+        server.await.unwrap();
+        let changes_before = backend.clipboard_change_count();
+        apply_rewrite(&backend, &rewrite, Duration::ZERO).unwrap();
+
+        assert_eq!(backend.value(), "Note: the quick fox.");
+        assert_eq!(backend.clipboard_change_count(), changes_before);
+    }
+
+    #[tokio::test]
+    async fn rich_selection_round_trips_through_markdown() {
+        let backend = FakeBackend::new("make this bold", 0..14)
+            .with_selection_html("make <b>this</b> bold");
+        let (base_url, server) = mock_llm("make **this** bolder").await;
+
+        let rewrite = run(&backend, &action(true), &provider(base_url), &RewriteSettings::default())
+        .await
+        .unwrap();
+        let pasted = apply_rewrite(&backend, &rewrite, Duration::ZERO).unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .ends_with(MARKDOWN_RULE));
+        assert_eq!(request["messages"][1]["content"], "Rewrite: make **this** bold");
+        assert!(rewrite.markdown);
+        assert_eq!(pasted, "make this bolder");
+        assert_eq!(backend.value(), "make this bolder");
+    }
+
+    #[tokio::test]
+    async fn app_override_changes_the_prompt() {
+        let backend = FakeBackend::new("hey can u send it", 0..17)
+            .with_app("Slack", "com.tinyspeck.slackmacgap");
+        let (base_url, server) = mock_llm("hey, can you send it?").await;
+        let action = RewriteAction {
+            app_overrides: vec![AppPromptOverride {
+                app: "Slack".into(),
+                system_prompt: Some("Keep it casual.".into()),
+                ..Default::default()
+            }],
+            ..action(false)
+        };
+
+        run(&backend, &action, &provider(base_url), &RewriteSettings::default())
+            .await
+            .unwrap();
+
+        let request = server.await.unwrap();
+        assert_eq!(request["messages"][0]["content"], "Keep it casual.\n\nOutput only the text.");
+    }
+
+    #[tokio::test]
+    async fn excluded_app_leaves_selection_alone() {
+        let backend = FakeBackend::new("ls -la", 0..6)
+            .with_app("Terminal", "com.apple.Terminal")
+            .with_clipboard_text("user clipboard");
+        let action = RewriteAction {
+            exclude_apps: vec!["com.apple.Terminal".into()],
+            ..action(false)
+        };
+        let provider = provider("http://127.0.0.1:9".into());
+
+        let err = run(&backend, &action, &provider, &RewriteSettings::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err, "'Fix Grammar' is disabled in Terminal");
+        assert_eq!(backend.clipboard_change_count(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn secure_field_never_reaches_the_llm() {
+        let backend = FakeBackend::new("hunter2", 0..7).with_secure_field();
+        // Nothing listens here; reaching the network would fail differently
+        let provider = provider("http://127.0.0.1:9".into());
+
+        let err = run(&backend, &action(false), &provider, &RewriteSettings::default())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "Cannot read secure input: The focused field is a password field"
+        );
+        assert_eq!(backend.value(), "hunter2");
+    }
+
+    #[tokio::test]
+    async fn secure_app_is_never_read() {
+        let backend = FakeBackend::new("vault item", 0..10)
+            .with_app("1Password", "com.1password.1password")
+            .with_direct_read();
+        let provider = provider("http://127.0.0.1:9".into());
+        let settings = RewriteSettings {
+            secure_apps: vec!["com.1password.1password".to_string()],
+            ..Default::default()
+        };
+
+        let err = run(&backend, &action(false), &provider, &settings)
+            .await
+            .unwrap_err();
+        assert!(err.contains("1Password is on the secure apps list"));
+        assert_eq!(backend.clipboard_change_count(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn empty_selection_keeps_previous_clipboard_out() {
+        let backend = FakeBackend::new("nothing selected", 4..4)
+            .with_clipboard_text("stale copy");
+        let provider = provider("http://127.0.0.1:9".into());
+
+        let err = run(&backend, &action(false), &provider, &RewriteSettings::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err, TextInteractionError::NoSelection.to_string());
+        assert_eq!(backend.read_clipboard_text().as_deref(), Some("stale copy"));
+    }
+
+    #[tokio::test]
+    async fn direct_read_skips_the_clipboard() {
+        let backend = FakeBackend::new("abc def", 4..7).with_direct_read();
+        let (base_url, server) = mock_llm("DEF").await;
+
+        let rewrite = run(&backend, &action(false), &provider(base_url), &RewriteSettings::default())
+        .await
+        .unwrap();
+        server.await.unwrap();
+
+        assert_eq!(rewrite.original_text, "def");
+        assert!(backend.clipboard_flavors().is_empty());
+    }
+
+    #[tokio::test]
+    async fn context_around_the_selection_fills_the_template() {
+        let backend = FakeBackend::new("Hi Sam, teh report is late. Sorry about that.", 8..27)
+            .with_direct_read();
+        let (base_url, server) = mock_llm("The report is late.").await;
+        let action = RewriteAction {
+            user_template: "[{{context_before}}] {{text}} [{{context_after}}]".into(),
+            context_chars: Some(12),
+            ..action(false)
+        };
+
+        run(&backend, &action, &provider(base_url), &RewriteSettings::default())
+            .await
+            .unwrap();
+
+        let request = server.await.unwrap();
+        assert_eq!(
+            request["messages"][1]["content"],
+            "[Hi Sam, ] teh report is late. [ Sorry about]"
+        );
+    }
+
+    #[tokio::test]
+    async fn template_fills_builtin_and_user_variables() {
+        let backend = FakeBackend::new("see you", 0..7)
+            .with_app("Mail", "com.apple.mail")
+            .with_clipboard_text("Q3 numbers");
+        let (base_url, server) = mock_llm("See you.").await;
+        let action = RewriteAction {
+            user_template: "{{text}} ({{app_name}}){{#if clipboard}} re: {{clipboard}}{{/if}}\n\
+                            {{#if tone}}Tone: {{tone}}{{else}}Neutral{{/if}} -- {{signature}}"
+                .into(),
+            ..action(false)
+        };
+        let settings = RewriteSettings {
+            variables: vec![
+                TemplateVariable {
+                    name: "signature".into(),
+                    value: "Sam".into(),
+                },
+                TemplateVariable {
+                    name: "tone".into(),
+                    value: String::new(),
+                },
+            ],
+            ..Default::default()
+        };
+
+        run(&backend, &action, &provider(base_url), &settings)
+            .await
+            .unwrap();
+
+        let request = server.await.unwrap();
+        assert_eq!(
+            request["messages"][1]["content"],
+            "see you (Mail) re: Q3 numbers\nNeutral -- Sam"
+        );
+    }
+
+    #[tokio::test]
+    async fn action_inputs_override_user_variables() {
+        let backend = FakeBackend::new("Guten Morgen", 0..12);
+        let (base_url, server) = mock_llm("Good morning").await;
+        let action = RewriteAction {
+            user_template: "Translate to {{target}}, {{tone}}: {{text}}".into(),
+            inputs: vec![
+                ActionInput {
+                    name: "target".into(),
+                    default: "English".into(),
+                    ..Default::default()
+                },
+                ActionInput {
+                    name: "tone".into(),
+                    kind: ActionInputKind::Choice,
+                    options: vec!["formal".into(), "casual".into()],
+                    ..Default::default()
+                },
+            ],
+            ..action(false)
+        };
+        let settings = RewriteSettings {
+            variables: vec![TemplateVariable {
+                name: "target".into(),
+                value: "Spanish".into(),
+            }],
+            inputs: HashMap::from([("target".to_string(), "French".to_string())]),
+            ..Default::default()
+        };
+
+        run(&backend, &action, &provider(base_url), &settings)
+            .await
+            .unwrap();
+
+        let request = server.await.unwrap();
+        assert_eq!(
+            request["messages"][1]["content"],
+            "Translate to French, formal: Guten Morgen"
+        );
+    }
+
+    #[test]
+    fn resolve_steps_looks_up_actions_and_providers() {
+        let proofread = RewriteAction {
+            id: "proofread".into(),
+            name: "Proofread".into(),
+            ..action(false)
+        };
+        let fast = ProviderConfig {
+            id: "fast".into(),
+            name: "Fast".into(),
+            ..provider("http://fast".into())
+        };
+        let chain = RewriteAction {
+            name: "Polish".into(),
+            steps: vec![
+                ActionStep {
+                    action_id: Some("proofread".into()),
+                    ..Default::default()
+                },
+                ActionStep {
+                    name: "Shorten".into(),
+                    system_prompt: "Shorten.".into(),
+                    user_template: "{{text}}".into(),
+                    provider_id: Some("fast".into()),
+                    model: Some("small-model".into()),
+                    ..Default::default()
+                },
+            ],
+            ..action(false)
+        };
+        let key = |p: &ProviderConfig| Some(format!("key-{}", p.id));
+        let actions = [proofread, chain.clone()];
+        let providers = [fast];
+
+        let default = provider("http://default".into());
+        let steps = resolve_steps(&chain, &actions, &providers, &default, key).unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].name, "Proofread");
+        assert_eq!(steps[0].prompts.system_prompt, "Fix grammar.");
+        assert_eq!(steps[0].provider.base_url, "http://default");
+        assert_eq!(steps[0].api_key, "key-mock");
+        assert_eq!(steps[1].name, "Shorten");
+        assert_eq!(steps[1].prompts.system_prompt, "Shorten.");
+        assert_eq!(steps[1].provider.model, "small-model");
+        assert_eq!(steps[1].api_key, "key-fast");
+
+        let nested = RewriteAction {
+            steps: vec![ActionStep {
+                action_id: Some(chain.id.clone()),
+                ..Default::default()
+            }],
+            ..chain.clone()
+        };
+        assert_eq!(
+            resolve_steps(&nested, &actions, &[], &provider("x".into()), key).unwrap_err(),
+            "Step 1 of 'Polish' can't run 'Polish', which has steps of its own"
+        );
+        assert_eq!(
+            resolve_steps(&chain, &[], &providers, &provider("x".into()), key).unwrap_err(),
+            "Step 1 of 'Polish' uses an action that no longer exists"
+        );
+        assert_eq!(
+            resolve_steps(&chain, &actions, &providers, &provider("x".into()), |_| None).unwrap_err(),
+            "No API key configured for mock. Go to Provider tab."
+        );
+    }
+
+    #[tokio::test]
+    async fn steps_rewrite_each_others_output() {
+        let backend = FakeBackend::new("teh long draft", 0..14);
+        let (first_url, first) = mock_llm("the long draft").await;
+        let (second_url, second) = mock_llm("the draft").await;
+        let action = RewriteAction {
+            steps: vec![
+                ActionStep {
+                    name: "Proofread".into(),
+                    system_prompt: "Fix spelling.".into(),
+                    user_template: "{{text}}".into(),
+                    ..Default::default()
+                },
+                ActionStep {
+                    name: "Shorten".into(),
+                    system_prompt: "Shorten.".into(),
+                    user_template: "Shorten: {{text}}".into(),
+                    provider_id: Some("second".into()),
+                    ..Default::default()
+                },
+            ],
+            ..action(false)
+        };
+        let providers = [ProviderConfig {
+            id: "second".into(),
+            name: "Second".into(),
+            ..provider(second_url)
+        }];
+        let steps = resolve_steps(&action, &[], &providers, &provider(first_url), |_| {
+            Some("sk-test".into())
+        })
+        .unwrap();
+
+        let rewrite = rewrite_selection(&backend, &action, &steps, &RewriteSettings::default())
+            .await
+            .unwrap();
+
+        assert_eq!(first.await.unwrap()["messages"][1]["content"], "teh long draft");
+        assert_eq!(
+            second.await.unwrap()["messages"][1]["content"],
+            "Shorten: the long draft"
+        );
+        assert_eq!(rewrite.original_text, "teh long draft");
+        assert_eq!(rewrite.response.text, "the draft");
+        assert_eq!(rewrite.response.tokens_used, Some(84));
+        let outputs: Vec<(&str, &str, &str)> = rewrite
+            .steps
+            .iter()
+            .map(|s| (s.name.as_str(), s.provider.as_str(), s.text.as_str()))
+            .collect();
+        assert_eq!(
+            outputs,
+            vec![("Proofread", "mock", "the long draft"), ("Shorten", "Second", "the draft")]
+        );
+    }
+
+    #[tokio::test]
+    async fn examples_precede_the_real_input() {
+        let backend = FakeBackend::new("teh fox", 0..7);
+        let (base_url, server) = mock_llm("the fox").await;
+        let action = RewriteAction {
+            examples: vec![
+                ("teh cat".into(), "the cat".into()),
+                ("a dgo".into(), "a dog".into()),
+            ],
+            ..action(false)
+        };
+
+        run(&backend, &action, &provider(base_url), &RewriteSettings::default())
+            .await
+            .unwrap();
+
+        let request = server.await.unwrap();
+        let messages: Vec<(&str, &str)> = request["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| (m["role"].as_str().unwrap(), m["content"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            messages[1..],
+            [
+                ("user", "Rewrite: teh cat"),
+                ("assistant", "the cat"),
+                ("user", "Rewrite: a dgo"),
+                ("assistant", "a dog"),
+                ("user", "Rewrite: teh fox"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn candidates_come_from_one_request_when_n_is_supported() {
+        let backend = FakeBackend::new("teh fox", 0..7);
+        let (base_url, server) = mock_llm_with(1, |request| {
+            let n = request["n"].as_u64().unwrap();
+            let choices: Vec<_> = (1..=n)
+                .map(|i| serde_json::json!({
+                    "message": { "role": "assistant", "content": format!("fox {}", i) }
+                }))
+                .collect();
+            (200, serde_json::json!({ "choices": choices, "usage": { "total_tokens": 30 } }))
+        })
+        .await;
+        let action = RewriteAction {
+            candidates: Some(3),
+            ..action(false)
+        };
+
+        let rewrite = run(&backend, &action, &provider(base_url), &RewriteSettings::default())
+            .await
+            .unwrap();
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests[0]["n"], 3);
+        assert_eq!(rewrite.candidates, vec!["fox 1", "fox 2", "fox 3"]);
+        assert_eq!(rewrite.response.text, "fox 1");
+        assert_eq!(rewrite.response.tokens_used, Some(30));
+    }
+
+    #[tokio::test]
+    async fn candidates_fall_back_to_parallel_requests() {
+        let backend = FakeBackend::new("teh fox", 0..7);
+        let (base_url, server) = mock_llm_with(3, |request| {
+            if request.get("n").is_some() {
+                return (400, serde_json::json!({ "error": { "message": "n is not supported" } }));
+            }
+            let reply = serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "the fox" } }],
+                "usage": { "total_tokens": 10 }
+            });
+            (200, reply)
+        })
+        .await;
+        let action = RewriteAction {
+            candidates: Some(2),
+            ..action(false)
+        };
+
+        let rewrite = run(&backend, &action, &provider(base_url), &RewriteSettings::default())
+            .await
+            .unwrap();
+
+        assert_eq!(server.await.unwrap().len(), 3);
+        assert_eq!(rewrite.candidates, vec!["the fox", "the fox"]);
+        assert_eq!(rewrite.response.tokens_used, Some(20));
+    }
+
+    #[tokio::test]
+    async fn candidates_return_other_bad_requests_without_retrying() {
+        let backend = FakeBackend::new("teh fox", 0..7);
+        let (base_url, server) = mock_llm_with(1, |_| {
+            let error = serde_json::json!({ "error": {
+                "message": "This model's maximum context length is 8192 tokens",
+                "param": "messages"
+            } });
+            (400, error)
+        })
+        .await;
+        let action = RewriteAction {
+            candidates: Some(3),
+            ..action(false)
+        };
+
+        let err = run(&backend, &action, &provider(base_url), &RewriteSettings::default())
+            .await
+            .unwrap_err();
+
+        assert_eq!(server.await.unwrap().len(), 1);
+        assert!(err.contains("maximum context length"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn invalid_template_fails_before_reading_the_selection() {
+        let backend = FakeBackend::new("text", 0..4);
+        let provider = provider("http://127.0.0.1:9".into());
+        let action = RewriteAction {
+            user_template: "{{#if text}}{{text}}".into(),
+            ..action(false)
+        };
+
+        let err = run(&backend, &action, &provider, &RewriteSettings::default())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "Invalid template in 'Fix Grammar': Line 1: {{#if text}} is missing its {{/if}}"
+        );
+        assert_eq!(backend.clipboard_change_count(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn empty_selection_falls_back_to_the_current_paragraph() {
+        let text = "Intro.\n\nteh first line\nand teh second\n\nOutro.";
+        let caret = text.find("first").unwrap();
+        let backend = FakeBackend::new(text, caret..caret).with_direct_read();
+        let (base_url, server) = mock_llm("The first line\nand the second").await;
+        let action = RewriteAction {
+            fallback_scope: FallbackScope::Paragraph,
+            ..action(false)
+        };
+
+        let rewrite = run(&backend, &action, &provider(base_url), &RewriteSettings::default())
+            .await
+            .unwrap();
+        server.await.unwrap();
+        apply_rewrite(&backend, &rewrite, Duration::ZERO).unwrap();
+
+        assert_eq!(rewrite.original_text, "teh first line\nand teh second");
+        assert_eq!(backend.value(), "Intro.\n\nThe first line\nand the second\n\nOutro.");
+    }
+
+    #[tokio::test]
+    async fn empty_selection_falls_back_to_the_whole_field() {
+        let backend = FakeBackend::new("teh whole field", 3..3);
+        let (base_url, server) = mock_llm("The whole field").await;
+        let action = RewriteAction {
+            fallback_scope: FallbackScope::Field,
+            ..action(false)
+        };
+
+        let rewrite = run(&backend, &action, &provider(base_url), &RewriteSettings::default())
+            .await
+            .unwrap();
+        server.await.unwrap();
+        apply_rewrite(&backend, &rewrite, Duration::ZERO).unwrap();
+
+        assert_eq!(rewrite.original_text, "teh whole field");
+        assert_eq!(backend.value(), "The whole field");
+    }
+}
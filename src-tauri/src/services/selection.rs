@@ -0,0 +1,278 @@
+use crate::models::{FallbackScope, SelectedText, TextInteractionError};
+use crate::services::clipboard::poll_until;
+use crate::services::markdown;
+use crate::services::text_backend::TextInteractionBackend;
+use std::ops::Range;
+use std::time::Duration;
+
+/// How long to wait for the target app to answer a simulated copy
+const COPY_TIMEOUT: Duration = Duration::from_millis(500);
+const COPY_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Selected text, read directly from the focused element when the backend
+/// can, otherwise through a simulated copy
+pub fn read_selected_text(backend: &dyn TextInteractionBackend) -> Result<String, String> {
+    if let Some(reason) = backend.secure_input_reason() {
+        return Err(TextInteractionError::SecureField(reason).to_string());
+    }
+
+    match backend.read_selection() {
+        Ok(text) if !text.is_empty() => return Ok(text),
+        _ => {}
+    }
+
+    copy_selection(backend, false).map(|(text, _)| text)
+}
+
+/// Copy the selection with its HTML/RTF flavor so formatting can be sent to
+/// the LLM as Markdown. Always goes through the clipboard; direct reads only
+/// expose plain text.
+pub fn read_rich_selection(backend: &dyn TextInteractionBackend) -> Result<SelectedText, String> {
+    if let Some(reason) = backend.secure_input_reason() {
+        return Err(TextInteractionError::SecureField(reason).to_string());
+    }
+
+    let app = backend.frontmost_app();
+    let (text, html) = copy_selection(backend, true)?;
+    let markdown = html
+        .map(|html| markdown::html_to_markdown(&html))
+        .filter(|md| !md.is_empty());
+    Ok(SelectedText {
+        text,
+        markdown,
+        app,
+    })
+}
+
+/// With nothing selected, select what `scope` covers around the caret so the
+/// selection can be read again
+pub fn select_fallback_scope(
+    backend: &dyn TextInteractionBackend,
+    scope: FallbackScope,
+) -> Result<(), String> {
+    let range = match scope {
+        FallbackScope::None => return Err(TextInteractionError::NoSelection.to_string()),
+        FallbackScope::Field => return backend.select_all(),
+        FallbackScope::Line | FallbackScope::Paragraph => {
+            let (text, selection) = backend
+                .field_text()
+                .ok_or("Can't read the focused field to find the text at the caret")?;
+            if scope == FallbackScope::Line {
+                line_range(&text, selection.start)
+            } else {
+                paragraph_range(&text, selection.start)
+            }
+        }
+    };
+    match range {
+        Some(range) => backend.select_range(range),
+        None => Err(TextInteractionError::NoSelection.to_string()),
+    }
+}
+
+/// Byte range of the line containing `caret`, without surrounding whitespace
+fn line_range(text: &str, caret: usize) -> Option<Range<usize>> {
+    trim_range(text, line_start(text, caret)..line_end(text, caret))
+}
+
+/// Byte range of the non-blank lines around `caret`, without surrounding
+/// whitespace; `None` on a blank line
+fn paragraph_range(text: &str, caret: usize) -> Option<Range<usize>> {
+    let is_blank = |range: Range<usize>| text[range].trim().is_empty();
+    let (mut start, mut end) = (line_start(text, caret), line_end(text, caret));
+    if is_blank(start..end) {
+        return None;
+    }
+    while start > 0 {
+        let prev = line_start(text, start - 1);
+        if is_blank(prev..start - 1) {
+            break;
+        }
+        start = prev;
+    }
+    while end < text.len() {
+        let next = line_end(text, end + 1);
+        if is_blank(end + 1..next) {
+            break;
+        }
+        end = next;
+    }
+    trim_range(text, start..end)
+}
+
+fn line_start(text: &str, at: usize) -> usize {
+    text[..at].rfind('\n').map_or(0, |i| i + 1)
+}
+
+fn line_end(text: &str, at: usize) -> usize {
+    text[at..].find('\n').map_or(text.len(), |i| at + i)
+}
+
+/// Shrink `range` past leading and trailing whitespace; `None` if nothing is left
+fn trim_range(text: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let content = &text[range.clone()];
+    let start = range.start + (content.len() - content.trim_start().len());
+    let end = range.end - (content.len() - content.trim_end().len());
+    (start < end).then_some(start..end)
+}
+
+/// Simulate a copy and read the plain text (and optionally HTML) that arrives,
+/// leaving the user's clipboard as it was
+fn copy_selection(
+    backend: &dyn TextInteractionBackend,
+    with_html: bool,
+) -> Result<(String, Option<String>), String> {
+    // Save every clipboard flavor, not just text
+    let saved_clipboard = backend.clipboard_snapshot();
+
+    // Without a change counter, write a sentinel we can tell apart from a copy
+    let change_count = backend.clipboard_change_count();
+    let sentinel = format!("omni-text-sentinel-{}", uuid::Uuid::new_v4());
+    if change_count.is_none() {
+        backend.write_clipboard_text(&sentinel)?;
+    }
+
+    if let Err(e) = backend.copy() {
+        // Don't leave the sentinel behind on the user's clipboard
+        let _ = backend.restore_clipboard(&saved_clipboard);
+        return Err(format!("Failed to simulate copy: {}", e));
+    }
+
+    // With nothing selected most apps leave the clipboard alone; never fall
+    // through to whatever was copied before
+    let copied = poll_until(COPY_TIMEOUT, COPY_POLL_INTERVAL, || match change_count {
+        Some(count) => backend.clipboard_change_count() != Some(count),
+        None => backend.read_clipboard_text().is_some_and(|t| t != sentinel),
+    });
+
+    // Read the new clipboard content; a non-text copy (e.g. an image) counts
+    // as no selection
+    let selected = if copied {
+        backend.read_clipboard_text().unwrap_or_default()
+    } else {
+        String::new()
+    };
+    let html = if copied && with_html {
+        backend.read_clipboard_html()
+    } else {
+        None
+    };
+
+    // Restore original clipboard
+    let _ = backend.restore_clipboard(&saved_clipboard);
+
+    if !selected.is_empty() {
+        Ok((selected, html))
+    } else {
+        Err(TextInteractionError::NoSelection.to_string())
+    }
+}
+
+/// Replace the current selection with `text`, writing it straight into the
+/// focused element when the backend can and pasting through the clipboard
+/// otherwise
+pub fn replace_text(
+    backend: &dyn TextInteractionBackend,
+    text: &str,
+    restore_delay: Duration,
+) -> Result<(), String> {
+    if backend.write_selection(text).is_ok() {
+        return Ok(());
+    }
+    paste_text(backend, text, restore_delay)
+}
+
+/// Replace the current selection in the frontmost app by pasting `text`,
+/// then put the user's previous clipboard contents back after `restore_delay`
+pub fn paste_text(
+    backend: &dyn TextInteractionBackend,
+    text: &str,
+    restore_delay: Duration,
+) -> Result<(), String> {
+    paste_with(backend, restore_delay, || backend.write_clipboard_text(text))
+}
+
+/// Paste Markdown as rich text; returns the plain-text flavor that was pasted
+pub fn paste_markdown(
+    backend: &dyn TextInteractionBackend,
+    text: &str,
+    restore_delay: Duration,
+) -> Result<String, String> {
+    let plain = markdown::markdown_to_plain(text);
+    let html = markdown::markdown_to_html(text);
+    paste_with(backend, restore_delay, || {
+        backend.write_clipboard_html(&html, &plain)
+    })?;
+    Ok(plain)
+}
+
+fn paste_with(
+    backend: &dyn TextInteractionBackend,
+    restore_delay: Duration,
+    write: impl FnOnce() -> Result<(), String>,
+) -> Result<(), String> {
+    let saved_clipboard = backend.clipboard_snapshot();
+
+    // Write replacement to clipboard
+    write()?;
+
+    if let Err(e) = backend.paste() {
+        let _ = backend.restore_clipboard(&saved_clipboard);
+        return Err(format!("Failed to simulate paste: {}", e));
+    }
+
+    // Some apps read the clipboard lazily after the paste shortcut, so
+    // restoring too early pastes the user's old clipboard instead
+    std::thread::sleep(restore_delay);
+    backend.restore_clipboard(&saved_clipboard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::text_backend::fake::FakeBackend;
+
+    const TEXT: &str = "Title\n\n  First line\nsecond line\n\nLast";
+
+    fn at(needle: &str) -> usize {
+        TEXT.find(needle).unwrap()
+    }
+
+    #[test]
+    fn line_range_stops_at_newlines_and_skips_indent() {
+        let range = line_range(TEXT, at("line\nsecond")).unwrap();
+        assert_eq!(&TEXT[range], "First line");
+        let range = line_range(TEXT, TEXT.len()).unwrap();
+        assert_eq!(&TEXT[range], "Last");
+        assert_eq!(line_range(TEXT, at("\n\n") + 1), None);
+    }
+
+    #[test]
+    fn paragraph_range_spans_non_blank_lines() {
+        let range = paragraph_range(TEXT, at("second")).unwrap();
+        assert_eq!(&TEXT[range], "First line\nsecond line");
+        let range = paragraph_range(TEXT, 0).unwrap();
+        assert_eq!(&TEXT[range], "Title");
+        assert_eq!(paragraph_range(TEXT, at("\n\nLast") + 1), None);
+    }
+
+    #[test]
+    fn failed_copy_leaves_no_sentinel_behind() {
+        let backend = FakeBackend::new("Hello world", 0..5)
+            .with_clipboard_text("saved")
+            .without_change_count()
+            .with_failing_shortcuts();
+        assert!(read_selected_text(&backend).is_err());
+        assert_eq!(backend.read_clipboard_text().as_deref(), Some("saved"));
+    }
+
+    #[test]
+    fn failed_paste_restores_the_clipboard() {
+        let backend = FakeBackend::new("Hello world", 0..5)
+            .with_clipboard_text("saved")
+            .with_failing_shortcuts();
+        assert!(paste_text(&backend, "Howdy", Duration::ZERO).is_err());
+        assert_eq!(backend.read_clipboard_text().as_deref(), Some("saved"));
+        assert_eq!(backend.value(), "Hello world");
+    }
+}
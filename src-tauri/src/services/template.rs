@@ -0,0 +1,332 @@
+use std::collections::{BTreeSet, HashMap};
+
+/// Variables every template can use; values are filled in when an action runs
+pub const BUILTIN_VARIABLES: &[&str] = &[
+    "text",
+    "app_name",
+    "date",
+    "clipboard",
+    "language",
+    "context_before",
+    "context_after",
+];
+
+/// A parsed prompt template.
+///
+/// - `{{name}}` inserts a variable, everywhere it appears
+/// - `{{#if name}}...{{else}}...{{/if}}` keeps the first branch when the
+///   variable is non-blank; `{{else}}` is optional and blocks nest
+/// - `\{{` is a literal `{{`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Var(String),
+    If {
+        name: String,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// An `{{#if}}` whose body is still being parsed
+struct OpenIf {
+    name: String,
+    line: usize,
+    /// Nodes before the `{{#if}}`, restored when it closes
+    outer: Vec<Node>,
+    /// The then-branch, once `{{else}}` has been seen
+    then: Option<Vec<Node>>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut stack: Vec<OpenIf> = Vec::new();
+        // Nodes of the innermost open branch
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut text = String::new();
+        let mut rest = source;
+
+        while let Some(pos) = rest.find("{{") {
+            // A backslash right before the braces makes them literal
+            if rest[..pos].ends_with('\\') {
+                text.push_str(&rest[..pos - 1]);
+                text.push_str("{{");
+                rest = &rest[pos + 2..];
+                continue;
+            }
+            text.push_str(&rest[..pos]);
+            let line = line_of(source, source.len() - rest.len() + pos);
+            let inner = &rest[pos + 2..];
+            let close = inner
+                .find("}}")
+                .ok_or_else(|| format!("Line {}: '{{{{' is never closed", line))?;
+            let tag = inner[..close].trim();
+            rest = &inner[close + 2..];
+
+            if !text.is_empty() {
+                nodes.push(Node::Text(std::mem::take(&mut text)));
+            }
+
+            if let Some(name) = tag.strip_prefix("#if ") {
+                let name = name.trim();
+                check_name(name, line)?;
+                stack.push(OpenIf {
+                    name: name.to_string(),
+                    line,
+                    outer: std::mem::take(&mut nodes),
+                    then: None,
+                });
+            } else if tag == "else" {
+                match stack.last_mut() {
+                    Some(open) if open.then.is_none() => {
+                        open.then = Some(std::mem::take(&mut nodes));
+                    }
+                    _ => return Err(format!("Line {}: {{{{else}}}} without {{{{#if}}}}", line)),
+                }
+            } else if tag == "/if" {
+                let open = stack
+                    .pop()
+                    .ok_or_else(|| format!("Line {}: {{{{/if}}}} without {{{{#if}}}}", line))?;
+                let branch = std::mem::replace(&mut nodes, open.outer);
+                let (then, otherwise) = match open.then {
+                    Some(then) => (then, branch),
+                    None => (branch, Vec::new()),
+                };
+                nodes.push(Node::If {
+                    name: open.name,
+                    then,
+                    otherwise,
+                });
+            } else {
+                check_name(tag, line)?;
+                nodes.push(Node::Var(tag.to_string()));
+            }
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+
+        if let Some(open) = stack.pop() {
+            return Err(format!(
+                "Line {}: {{{{#if {}}}}} is missing its {{{{/if}}}}",
+                open.line, open.name
+            ));
+        }
+        Ok(Self { nodes })
+    }
+
+    /// Every variable the template refers to, in inserts and conditions
+    pub fn variables(&self) -> BTreeSet<&str> {
+        fn collect<'a>(nodes: &'a [Node], names: &mut BTreeSet<&'a str>) {
+            for node in nodes {
+                match node {
+                    Node::Text(_) => {}
+                    Node::Var(name) => {
+                        names.insert(name);
+                    }
+                    Node::If {
+                        name,
+                        then,
+                        otherwise,
+                    } => {
+                        names.insert(name);
+                        collect(then, names);
+                        collect(otherwise, names);
+                    }
+                }
+            }
+        }
+        let mut names = BTreeSet::new();
+        collect(&self.nodes, &mut names);
+        names
+    }
+
+    pub fn uses(&self, name: &str) -> bool {
+        self.variables().contains(name)
+    }
+
+    /// Fill in the template. Values are inserted as written, never parsed as
+    /// template syntax themselves.
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<String, String> {
+        fn render_into(
+            nodes: &[Node],
+            values: &HashMap<String, String>,
+            out: &mut String,
+        ) -> Result<(), String> {
+            let lookup = |name: &str| {
+                values
+                    .get(name)
+                    .ok_or_else(|| format!("Unknown variable '{}'", name))
+            };
+            for node in nodes {
+                match node {
+                    Node::Text(text) => out.push_str(text),
+                    Node::Var(name) => out.push_str(lookup(name)?),
+                    Node::If {
+                        name,
+                        then,
+                        otherwise,
+                    } => {
+                        let branch = if lookup(name)?.trim().is_empty() {
+                            otherwise
+                        } else {
+                            then
+                        };
+                        render_into(branch, values, out)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        let mut out = String::new();
+        render_into(&self.nodes, values, &mut out)?;
+        Ok(out)
+    }
+}
+
+/// Parse `source` and check it only uses built-in variables or `extra` ones
+pub fn validate(source: &str, extra: &[String]) -> Result<(), String> {
+    let template = Template::parse(source)?;
+    let unknown: Vec<&str> = template
+        .variables()
+        .into_iter()
+        .filter(|name| !BUILTIN_VARIABLES.contains(name) && !extra.iter().any(|e| e == name))
+        .collect();
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Unknown variable: {}", unknown.join(", ")))
+    }
+}
+
+/// Variable names are letters, digits and underscores, not starting with a digit
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn check_name(name: &str, line: usize) -> Result<(), String> {
+    if is_valid_name(name) {
+        Ok(())
+    } else {
+        Err(format!("Line {}: '{{{{{}}}}}' is not a valid variable", line, name))
+    }
+}
+
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn render(source: &str, pairs: &[(&str, &str)]) -> Result<String, String> {
+        Template::parse(source)?.render(&values(pairs))
+    }
+
+    #[test]
+    fn replaces_every_occurrence_and_allows_spaces() {
+        assert_eq!(
+            render("{{text}} / {{ text }} in {{app_name}}", &[("text", "hi"), ("app_name", "Mail")]),
+            Ok("hi / hi in Mail".into())
+        );
+    }
+
+    #[test]
+    fn values_are_not_parsed_again() {
+        assert_eq!(
+            render("{{text}}", &[("text", "{{clipboard}} \\{{x}}")]),
+            Ok("{{clipboard}} \\{{x}}".into())
+        );
+    }
+
+    #[test]
+    fn conditionals_pick_a_branch_and_nest() {
+        let source = "{{#if context_before}}Before: {{context_before}}\n{{else}}No context\n{{/if}}\
+                      {{#if app_name}}{{#if language}}[{{language}}]{{/if}}{{/if}}{{text}}";
+        assert_eq!(
+            render(
+                source,
+                &[("context_before", "Dear Sam,"), ("app_name", "Mail"), ("language", "de"), ("text", "x")]
+            ),
+            Ok("Before: Dear Sam,\n[de]x".into())
+        );
+        assert_eq!(
+            render(
+                source,
+                &[("context_before", "  "), ("app_name", ""), ("language", "de"), ("text", "x")]
+            ),
+            Ok("No context\nx".into())
+        );
+    }
+
+    #[test]
+    fn backslash_escapes_braces() {
+        assert_eq!(
+            render("Use \\{{text}} literally: {{text}}", &[("text", "hi")]),
+            Ok("Use {{text}} literally: hi".into())
+        );
+    }
+
+    #[test]
+    fn lists_variables_in_conditions_too() {
+        let template = Template::parse("{{#if clipboard}}{{signature}}{{/if}}{{text}}").unwrap();
+        let names: Vec<&str> = template.variables().into_iter().collect();
+        assert_eq!(names, vec!["clipboard", "signature", "text"]);
+        assert!(template.uses("clipboard"));
+        assert!(!template.uses("date"));
+    }
+
+    #[test]
+    fn syntax_errors_name_the_line() {
+        assert_eq!(
+            Template::parse("a\n{{#if text}}b").unwrap_err(),
+            "Line 2: {{#if text}} is missing its {{/if}}"
+        );
+        assert_eq!(
+            Template::parse("{{text}}\n\n{{/if}}").unwrap_err(),
+            "Line 3: {{/if}} without {{#if}}"
+        );
+        assert_eq!(
+            Template::parse("{{#if text}}{{else}}{{else}}{{/if}}").unwrap_err(),
+            "Line 1: {{else}} without {{#if}}"
+        );
+        assert_eq!(Template::parse("{{text").unwrap_err(), "Line 1: '{{' is never closed");
+        assert_eq!(
+            Template::parse("{{my var}}").unwrap_err(),
+            "Line 1: '{{my var}}' is not a valid variable"
+        );
+    }
+
+    #[test]
+    fn validate_accepts_builtin_and_user_variables() {
+        let user = vec!["signature".to_string()];
+        assert!(validate("{{text}} {{date}} {{signature}}", &user).is_ok());
+        assert_eq!(
+            validate("{{text}} {{signatur}} {{tone}}", &user).unwrap_err(),
+            "Unknown variable: signatur, tone"
+        );
+    }
+
+    #[test]
+    fn render_fails_on_missing_values() {
+        assert_eq!(render("{{text}}", &[]).unwrap_err(), "Unknown variable 'text'");
+    }
+}
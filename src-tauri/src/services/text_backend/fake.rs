@@ -0,0 +1,291 @@
+use super::{clip_context, ClipboardSnapshot, TextInteractionBackend};
+use crate::models::{AppContext, SelectionContext};
+use std::ops::Range;
+use std::sync::Mutex;
+
+const PLAIN: &str = "public.utf8-plain-text";
+const HTML: &str = "public.html";
+
+/// In-memory stand-in for the OS: one focused text field with a selection,
+/// a clipboard with typed flavors and a change counter. Copy and paste act
+/// on the field the way a real app would, so the selection flow above the
+/// backend can run headless.
+pub struct FakeBackend {
+    state: Mutex<FakeState>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    value: String,
+    /// Byte range of the selection in `value`
+    selection: Range<usize>,
+    /// HTML flavor a copy of the current selection puts on the clipboard
+    selection_html: Option<String>,
+    secure: Option<String>,
+    /// Whether the selection and the field's value can be read without the
+    /// clipboard, like AX
+    direct_read: bool,
+    /// Whether the selection can be replaced without the clipboard
+    direct_write: bool,
+    /// Whether copy and paste shortcuts fail, like a lost virtual keyboard
+    failing_shortcuts: bool,
+    /// Whether the clipboard lacks a change counter, like X11 and Wayland
+    no_change_count: bool,
+    clipboard: Vec<(String, Vec<u8>)>,
+    change_count: i64,
+    pid: Option<i32>,
+    app: Option<AppContext>,
+}
+
+impl FakeBackend {
+    /// A focused field holding `value` with `selection` (byte offsets) selected
+    pub fn new(value: &str, selection: Range<usize>) -> Self {
+        assert!(selection.end <= value.len(), "selection out of bounds");
+        Self {
+            state: Mutex::new(FakeState {
+                value: value.to_string(),
+                selection,
+                pid: Some(4242),
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn with_secure_field(self) -> Self {
+        self.state.lock().unwrap().secure = Some("The focused field is a password field".into());
+        self
+    }
+
+    pub fn with_direct_read(self) -> Self {
+        self.state.lock().unwrap().direct_read = true;
+        self
+    }
+
+    pub fn with_direct_write(self) -> Self {
+        self.state.lock().unwrap().direct_write = true;
+        self
+    }
+
+    pub fn with_failing_shortcuts(self) -> Self {
+        self.state.lock().unwrap().failing_shortcuts = true;
+        self
+    }
+
+    pub fn without_change_count(self) -> Self {
+        self.state.lock().unwrap().no_change_count = true;
+        self
+    }
+
+    pub fn with_app(self, name: &str, bundle_id: &str) -> Self {
+        self.state.lock().unwrap().app = Some(AppContext {
+            name: name.to_string(),
+            bundle_id: Some(bundle_id.to_string()),
+            window_title: None,
+        });
+        self
+    }
+
+    pub fn with_selection_html(self, html: &str) -> Self {
+        self.state.lock().unwrap().selection_html = Some(html.to_string());
+        self
+    }
+
+    pub fn with_clipboard_text(self, text: &str) -> Self {
+        self.state.lock().unwrap().clipboard = vec![(PLAIN.into(), text.as_bytes().to_vec())];
+        self
+    }
+
+    /// Current contents of the focused field
+    pub fn value(&self) -> String {
+        self.state.lock().unwrap().value.clone()
+    }
+
+    pub fn selected_text(&self) -> String {
+        let state = self.state.lock().unwrap();
+        state.value[state.selection.clone()].to_string()
+    }
+
+    pub fn clipboard_flavors(&self) -> Vec<(String, Vec<u8>)> {
+        self.state.lock().unwrap().clipboard.clone()
+    }
+}
+
+impl FakeState {
+    fn flavor(&self, kind: &str) -> Option<String> {
+        self.clipboard
+            .iter()
+            .find(|(t, _)| t == kind)
+            .map(|(_, data)| String::from_utf8_lossy(data).into_owned())
+    }
+
+    /// Replace the selection and leave the cursor after the new text
+    fn replace_selection(&mut self, text: &str) {
+        let range = self.selection.clone();
+        self.value.replace_range(range.clone(), text);
+        let cursor = range.start + text.len();
+        self.selection = cursor..cursor;
+    }
+
+    fn set_clipboard(&mut self, flavors: Vec<(String, Vec<u8>)>) {
+        self.clipboard = flavors;
+        self.change_count += 1;
+    }
+}
+
+impl TextInteractionBackend for FakeBackend {
+    fn has_permission(&self) -> bool {
+        true
+    }
+
+    fn request_permission(&self) {}
+
+    fn read_selection(&self) -> Result<String, String> {
+        if self.state.lock().unwrap().direct_read {
+            Ok(self.selected_text())
+        } else {
+            Err("Direct selection reads are disabled".into())
+        }
+    }
+
+    fn selection_context(&self, max_chars: usize) -> Option<SelectionContext> {
+        let state = self.state.lock().unwrap();
+        state.direct_read.then(|| {
+            clip_context(
+                &state.value[..state.selection.start],
+                &state.value[state.selection.end..],
+                max_chars,
+            )
+        })
+    }
+
+    fn field_text(&self) -> Option<(String, Range<usize>)> {
+        let state = self.state.lock().unwrap();
+        state
+            .direct_read
+            .then(|| (state.value.clone(), state.selection.clone()))
+    }
+
+    fn select_range(&self, range: Range<usize>) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if state.value.get(range.clone()).is_none() {
+            return Err(format!("Range {:?} is outside the field", range));
+        }
+        state.selection = range;
+        Ok(())
+    }
+
+    fn select_all(&self) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        state.selection = 0..state.value.len();
+        Ok(())
+    }
+
+    fn secure_input_reason(&self) -> Option<String> {
+        self.state.lock().unwrap().secure.clone()
+    }
+
+    fn focused_app_pid(&self) -> Option<i32> {
+        self.state.lock().unwrap().pid
+    }
+
+    fn frontmost_app(&self) -> Option<AppContext> {
+        self.state.lock().unwrap().app.clone()
+    }
+
+    fn activate_app(&self, _pid: i32) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn select_text(&self, text: &str) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let start = state
+            .value
+            .rfind(text)
+            .ok_or_else(|| format!("'{}' not found in field", text))?;
+        state.selection = start..start + text.len();
+        Ok(())
+    }
+
+    fn write_selection(&self, text: &str) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if !state.direct_write {
+            return Err("Direct selection writes are disabled".into());
+        }
+        state.replace_selection(text);
+        Ok(())
+    }
+
+    fn copy(&self) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if state.failing_shortcuts {
+            return Err("Copy shortcut failed".into());
+        }
+        // Like most apps, copying an empty selection leaves the clipboard alone
+        if state.selection.is_empty() {
+            return Ok(());
+        }
+        let text = state.value[state.selection.clone()].to_string();
+        let mut flavors = vec![(PLAIN.to_string(), text.into_bytes())];
+        if let Some(html) = state.selection_html.clone() {
+            flavors.push((HTML.to_string(), html.into_bytes()));
+        }
+        state.set_clipboard(flavors);
+        Ok(())
+    }
+
+    fn paste(&self) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if state.failing_shortcuts {
+            return Err("Paste shortcut failed".into());
+        }
+        let text = state.flavor(PLAIN).unwrap_or_default();
+        state.replace_selection(&text);
+        Ok(())
+    }
+
+    fn clipboard_snapshot(&self) -> ClipboardSnapshot {
+        let state = self.state.lock().unwrap();
+        ClipboardSnapshot {
+            items: if state.clipboard.is_empty() {
+                Vec::new()
+            } else {
+                vec![state.clipboard.clone()]
+            },
+        }
+    }
+
+    fn restore_clipboard(&self, snapshot: &ClipboardSnapshot) -> Result<(), String> {
+        let flavors = snapshot.items.first().cloned().unwrap_or_default();
+        self.state.lock().unwrap().set_clipboard(flavors);
+        Ok(())
+    }
+
+    fn clipboard_change_count(&self) -> Option<i64> {
+        let state = self.state.lock().unwrap();
+        (!state.no_change_count).then_some(state.change_count)
+    }
+
+    fn read_clipboard_text(&self) -> Option<String> {
+        self.state.lock().unwrap().flavor(PLAIN)
+    }
+
+    fn read_clipboard_html(&self) -> Option<String> {
+        self.state.lock().unwrap().flavor(HTML)
+    }
+
+    fn write_clipboard_text(&self, text: &str) -> Result<(), String> {
+        self.state
+            .lock()
+            .unwrap()
+            .set_clipboard(vec![(PLAIN.into(), text.as_bytes().to_vec())]);
+        Ok(())
+    }
+
+    fn write_clipboard_html(&self, html: &str, plain: &str) -> Result<(), String> {
+        self.state.lock().unwrap().set_clipboard(vec![
+            (PLAIN.into(), plain.as_bytes().to_vec()),
+            (HTML.into(), html.as_bytes().to_vec()),
+        ]);
+        Ok(())
+    }
+}
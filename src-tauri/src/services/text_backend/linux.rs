@@ -0,0 +1,318 @@
+use super::{ClipboardSnapshot, TextInteractionBackend};
+use crate::models::{AppContext, SelectionContext};
+use crate::services::clipboard;
+use std::io::Write;
+use std::ops::Range;
+use std::process::{Command, Stdio};
+use tauri::AppHandle;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+/// How a clipboard snapshot can be put back. `xclip` and `wl-copy` serve a
+/// single type per owner process and the clipboard plugin only plain text
+/// with an optional HTML flavor, so anything richer can't be restored whole.
+enum RestorePlan<'a> {
+    /// Plain text and/or HTML, through the clipboard plugin
+    Text {
+        plain: Option<&'a [u8]>,
+        html: Option<&'a [u8]>,
+    },
+    /// The snapshot's only flavor, through the clipboard tool
+    Single(&'a str, &'a [u8]),
+}
+
+fn restore_plan(flavors: &[(String, Vec<u8>)]) -> Option<RestorePlan<'_>> {
+    let find = |wanted: fn(&str) -> bool| {
+        flavors
+            .iter()
+            .find(|(mime, _)| wanted(mime))
+            .map(|(_, data)| data.as_slice())
+    };
+    let is_plain = |mime: &str| mime.starts_with("text/plain");
+    let is_html = |mime: &str| mime == "text/html";
+    if flavors.iter().all(|(mime, _)| is_plain(mime) || is_html(mime)) {
+        return Some(RestorePlan::Text {
+            plain: find(is_plain),
+            html: find(is_html),
+        });
+    }
+    match flavors {
+        [(mime, data)] => Some(RestorePlan::Single(mime, data)),
+        _ => None,
+    }
+}
+
+/// X11 and Wayland via the usual command-line tools: `xclip`/`xdotool` on
+/// X11, `wl-clipboard`/`wtype` on Wayland. There is no accessibility API to
+/// read the selection, so it always goes through a copy; shortcuts use Ctrl.
+pub struct LinuxBackend {
+    app: AppHandle,
+    wayland: bool,
+}
+
+impl LinuxBackend {
+    pub fn new(app: AppHandle) -> Self {
+        Self {
+            app,
+            wayland: std::env::var_os("WAYLAND_DISPLAY").is_some(),
+        }
+    }
+
+    fn paste_tool(&self, mime: Option<&str>) -> Result<Vec<u8>, String> {
+        let mut args: Vec<&str> = Vec::new();
+        if self.wayland {
+            args.push("--no-newline");
+            if let Some(mime) = mime {
+                args.extend(["--type", mime]);
+            }
+            run("wl-paste", &args)
+        } else {
+            args.extend(["-o", "-selection", "clipboard"]);
+            if let Some(mime) = mime {
+                args.extend(["-t", mime]);
+            }
+            run("xclip", &args)
+        }
+    }
+
+    fn list_clipboard_types(&self) -> Vec<String> {
+        let output = if self.wayland {
+            run("wl-paste", &["--list-types"])
+        } else {
+            run("xclip", &["-o", "-selection", "clipboard", "-t", "TARGETS"])
+        };
+        output
+            .map(|bytes| {
+                String::from_utf8_lossy(&bytes)
+                    .lines()
+                    .map(str::trim)
+                    // Skip X11 bookkeeping atoms like TARGETS and TIMESTAMP
+                    .filter(|t| t.contains('/'))
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn send_shortcut(&self, key: &str) -> Result<(), String> {
+        if self.wayland {
+            run("wtype", &["-M", "ctrl", key, "-m", "ctrl"]).map(|_| ())
+        } else {
+            match key {
+                "a" => clipboard::simulate_select_all(),
+                "c" => clipboard::simulate_copy(),
+                _ => clipboard::simulate_paste(),
+            }
+        }
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+fn run_with_input(program: &str, args: &[&str], input: &[u8]) -> Result<(), String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input)
+            .map_err(|e| format!("Failed to write to {}: {}", program, e))?;
+    }
+    // Both tools fork a background owner and exit once they have the data
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for {}: {}", program, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} exited with {}", program, status))
+    }
+}
+
+impl TextInteractionBackend for LinuxBackend {
+    fn has_permission(&self) -> bool {
+        // No permission gate for synthetic input on X11; Wayland tools rely on
+        // the compositor allowing virtual keyboards
+        true
+    }
+
+    fn request_permission(&self) {}
+
+    fn read_selection(&self) -> Result<String, String> {
+        // PRIMARY keeps whatever was last highlighted in any window, so it
+        // can't tell whether the focused field has a selection; copy instead
+        Err("Reading the selection directly is not supported on Linux".into())
+    }
+
+    fn selection_context(&self, _max_chars: usize) -> Option<SelectionContext> {
+        None
+    }
+
+    fn field_text(&self) -> Option<(String, Range<usize>)> {
+        None
+    }
+
+    fn select_range(&self, _range: Range<usize>) -> Result<(), String> {
+        Err("Selecting a range is not supported on Linux".into())
+    }
+
+    fn select_all(&self) -> Result<(), String> {
+        self.send_shortcut("a")
+    }
+
+    fn secure_input_reason(&self) -> Option<String> {
+        // Neither X11 nor Wayland exposes a portable secure-input flag;
+        // toolkits refuse to copy out of password fields instead
+        None
+    }
+
+    fn focused_app_pid(&self) -> Option<i32> {
+        if self.wayland {
+            return None;
+        }
+        run("xdotool", &["getactivewindow", "getwindowpid"])
+            .ok()
+            .and_then(|out| String::from_utf8_lossy(&out).trim().parse().ok())
+    }
+
+    fn frontmost_app(&self) -> Option<AppContext> {
+        if self.wayland {
+            // Wayland keeps the focused window private to the compositor
+            return None;
+        }
+        let xdotool = |command: &str| {
+            run("xdotool", &["getactivewindow", command])
+                .ok()
+                .map(|out| String::from_utf8_lossy(&out).trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        let pid = self.focused_app_pid()?;
+        let name = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+        Some(AppContext {
+            name: name.trim().to_string(),
+            bundle_id: xdotool("getwindowclassname"),
+            window_title: xdotool("getwindowname"),
+        })
+    }
+
+    fn activate_app(&self, pid: i32) -> Result<(), String> {
+        if self.wayland {
+            return Err("Activating other apps is not supported on Wayland".into());
+        }
+        let pid = pid.to_string();
+        run(
+            "xdotool",
+            &["search", "--limit", "1", "--pid", &pid, "windowactivate", "--sync"],
+        )
+        .map(|_| ())
+    }
+
+    fn select_text(&self, _text: &str) -> Result<(), String> {
+        Err("Reselecting text is not supported on Linux".into())
+    }
+
+    fn write_selection(&self, _text: &str) -> Result<(), String> {
+        // No toolkit-neutral way to edit another app's field; always paste
+        Err("Writing the selection directly is not supported on Linux".into())
+    }
+
+    fn copy(&self) -> Result<(), String> {
+        self.send_shortcut("c")
+    }
+
+    fn paste(&self) -> Result<(), String> {
+        self.send_shortcut("v")
+    }
+
+    fn clipboard_snapshot(&self) -> ClipboardSnapshot {
+        let flavors: Vec<(String, Vec<u8>)> = self
+            .list_clipboard_types()
+            .into_iter()
+            .filter_map(|mime| {
+                let data = self.paste_tool(Some(&mime)).ok()?;
+                Some((mime, data))
+            })
+            .collect();
+        ClipboardSnapshot {
+            items: if flavors.is_empty() { Vec::new() } else { vec![flavors] },
+        }
+    }
+
+    fn restore_clipboard(&self, snapshot: &ClipboardSnapshot) -> Result<(), String> {
+        let Some(flavors) = snapshot.items.first() else {
+            return if self.wayland {
+                run("wl-copy", &["--clear"]).map(|_| ())
+            } else {
+                self.write_clipboard_text("")
+            };
+        };
+        match restore_plan(flavors) {
+            Some(RestorePlan::Text { plain, html }) => {
+                let text = |data: Option<&[u8]>| {
+                    data.map(|d| String::from_utf8_lossy(d).into_owned())
+                        .unwrap_or_default()
+                };
+                match html {
+                    Some(_) => self.write_clipboard_html(&text(html), &text(plain)),
+                    None => self.write_clipboard_text(&text(plain)),
+                }
+            }
+            Some(RestorePlan::Single(mime, data)) => {
+                if self.wayland {
+                    run_with_input("wl-copy", &["--type", mime], data)
+                } else {
+                    run_with_input("xclip", &["-i", "-selection", "clipboard", "-t", mime], data)
+                }
+            }
+            // Restoring one flavor would silently drop the others (say an
+            // image and its file URI), so the rewrite is left on the clipboard
+            None => Ok(()),
+        }
+    }
+
+    fn clipboard_change_count(&self) -> Option<i64> {
+        // No counter on X11/Wayland; the selection flow falls back to a sentinel
+        None
+    }
+
+    fn read_clipboard_text(&self) -> Option<String> {
+        self.app.clipboard().read_text().ok()
+    }
+
+    fn read_clipboard_html(&self) -> Option<String> {
+        self.paste_tool(Some("text/html"))
+            .ok()
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .filter(|html| !html.is_empty())
+    }
+
+    fn write_clipboard_text(&self, text: &str) -> Result<(), String> {
+        self.app
+            .clipboard()
+            .write_text(text)
+            .map_err(|e| format!("Failed to write to clipboard: {}", e))
+    }
+
+    fn write_clipboard_html(&self, html: &str, plain: &str) -> Result<(), String> {
+        self.app
+            .clipboard()
+            .write_html(html, Some(plain))
+            .map_err(|e| format!("Failed to write to clipboard: {}", e))
+    }
+}
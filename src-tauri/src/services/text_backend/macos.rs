@@ -0,0 +1,119 @@
+use super::{ClipboardSnapshot, TextInteractionBackend};
+use crate::models::{AppContext, SelectionContext};
+use crate::services::{accessibility, clipboard, pasteboard, workspace};
+use std::ops::Range;
+use tauri::AppHandle;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+/// Accessibility API for the focused element, NSPasteboard for the clipboard
+/// and Cmd-based shortcuts
+pub struct MacBackend {
+    app: AppHandle,
+}
+
+impl MacBackend {
+    pub fn new(app: AppHandle) -> Self {
+        Self { app }
+    }
+}
+
+impl TextInteractionBackend for MacBackend {
+    fn has_permission(&self) -> bool {
+        accessibility::check_permission()
+    }
+
+    fn request_permission(&self) {
+        accessibility::request_permission();
+    }
+
+    fn read_selection(&self) -> Result<String, String> {
+        accessibility::get_selected_text_ax()
+    }
+
+    fn selection_context(&self, max_chars: usize) -> Option<SelectionContext> {
+        accessibility::selection_context(max_chars)
+    }
+
+    fn field_text(&self) -> Option<(String, Range<usize>)> {
+        accessibility::focused_field_text()
+    }
+
+    fn select_range(&self, range: Range<usize>) -> Result<(), String> {
+        accessibility::select_range_in_focused_element(range)
+    }
+
+    fn select_all(&self) -> Result<(), String> {
+        clipboard::simulate_select_all()
+    }
+
+    fn secure_input_reason(&self) -> Option<String> {
+        accessibility::secure_input_reason()
+    }
+
+    fn focused_app_pid(&self) -> Option<i32> {
+        accessibility::focused_app_pid()
+    }
+
+    fn frontmost_app(&self) -> Option<AppContext> {
+        let app = workspace::frontmost_app()?;
+        Some(AppContext {
+            name: app.name,
+            bundle_id: app.bundle_id,
+            window_title: accessibility::focused_window_title(app.pid),
+        })
+    }
+
+    fn activate_app(&self, pid: i32) -> Result<(), String> {
+        accessibility::activate_app(pid)
+    }
+
+    fn select_text(&self, text: &str) -> Result<(), String> {
+        accessibility::select_text_in_focused_element(text)
+    }
+
+    fn write_selection(&self, text: &str) -> Result<(), String> {
+        accessibility::replace_selected_text_ax(text)
+    }
+
+    fn copy(&self) -> Result<(), String> {
+        clipboard::simulate_copy()
+    }
+
+    fn paste(&self) -> Result<(), String> {
+        clipboard::simulate_paste()
+    }
+
+    fn clipboard_snapshot(&self) -> ClipboardSnapshot {
+        pasteboard::snapshot()
+    }
+
+    fn restore_clipboard(&self, snapshot: &ClipboardSnapshot) -> Result<(), String> {
+        pasteboard::restore(snapshot)
+    }
+
+    fn clipboard_change_count(&self) -> Option<i64> {
+        Some(pasteboard::change_count() as i64)
+    }
+
+    fn read_clipboard_text(&self) -> Option<String> {
+        self.app.clipboard().read_text().ok()
+    }
+
+    fn read_clipboard_html(&self) -> Option<String> {
+        pasteboard::read_html()
+    }
+
+    fn write_clipboard_text(&self, text: &str) -> Result<(), String> {
+        self.app
+            .clipboard()
+            .write_text(text)
+            .map_err(|e| format!("Failed to write to clipboard: {}", e))
+    }
+
+    fn write_clipboard_html(&self, html: &str, plain: &str) -> Result<(), String> {
+        self.app
+            .clipboard()
+            .write_html(html, Some(plain))
+            .map_err(|e| format!("Failed to write to clipboard: {}", e))
+    }
+}
@@ -0,0 +1,147 @@
+use crate::models::{AppContext, SelectionContext};
+use std::ops::Range;
+use std::sync::Arc;
+
+#[cfg(test)]
+pub mod fake;
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "linux")]
+pub use linux::LinuxBackend as PlatformBackend;
+#[cfg(target_os = "macos")]
+pub use macos::MacBackend as PlatformBackend;
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+compile_error!("Omni Text has text interaction backends for macOS and Linux only");
+
+/// Backend shared through Tauri state; the platform one in the app, a fake in tests
+pub type SharedBackend = Arc<dyn TextInteractionBackend>;
+
+/// Every clipboard item with the raw data of each of its types (UTIs on
+/// macOS, MIME types on Linux)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClipboardSnapshot {
+    pub items: Vec<Vec<(String, Vec<u8>)>>,
+}
+
+impl ClipboardSnapshot {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/// The last `max_chars` characters of `before` and the first `max_chars` of
+/// `after`. A word cut in half at the limit is dropped.
+pub fn clip_context(before: &str, after: &str, max_chars: usize) -> SelectionContext {
+    let start = match max_chars.checked_sub(1) {
+        Some(n) => before.char_indices().rev().nth(n).map_or(0, |(i, _)| i),
+        None => before.len(),
+    };
+    let mut clipped_before = &before[start..];
+    let cut_word = before[..start].ends_with(|c: char| !c.is_whitespace())
+        && clipped_before.starts_with(|c: char| !c.is_whitespace());
+    if cut_word {
+        if let Some(space) = clipped_before.find(char::is_whitespace) {
+            clipped_before = clipped_before[space..].trim_start();
+        }
+    }
+
+    let end = after.char_indices().nth(max_chars).map_or(after.len(), |(i, _)| i);
+    let mut clipped_after = &after[..end];
+    let cut_word = after[end..].starts_with(|c: char| !c.is_whitespace())
+        && clipped_after.ends_with(|c: char| !c.is_whitespace());
+    if cut_word {
+        if let Some(space) = clipped_after.rfind(char::is_whitespace) {
+            clipped_after = clipped_after[..space].trim_end();
+        }
+    }
+
+    SelectionContext {
+        before: clipped_before.to_string(),
+        after: clipped_after.to_string(),
+    }
+}
+
+/// Platform access to the focused text field, the clipboard and keystrokes.
+/// The copy/paste flow built on top lives in `services::selection`.
+pub trait TextInteractionBackend: Send + Sync {
+    /// Whether the OS lets us read other apps' UI and send keystrokes
+    fn has_permission(&self) -> bool;
+    /// Show the OS prompt for the permission, where there is one
+    fn request_permission(&self);
+
+    /// Read the selection straight from the focused element, without the clipboard
+    fn read_selection(&self) -> Result<String, String>;
+    /// Up to `max_chars` characters on each side of the selection in the
+    /// focused field, where its value can be read
+    fn selection_context(&self, max_chars: usize) -> Option<SelectionContext>;
+    /// Full text of the focused field and the selection in it, as byte
+    /// offsets into the text
+    fn field_text(&self) -> Option<(String, Range<usize>)>;
+    /// Select a byte range of the text `field_text` returned
+    fn select_range(&self, range: Range<usize>) -> Result<(), String>;
+    /// Send the platform's select-all shortcut to the frontmost app
+    fn select_all(&self) -> Result<(), String>;
+    /// Why the focused element must not be read or typed into, e.g. a
+    /// password field; `None` when it is safe
+    fn secure_input_reason(&self) -> Option<String>;
+    /// Process id of the app that owns the focused element
+    fn focused_app_pid(&self) -> Option<i32>;
+    /// Name, identifier and window title of the frontmost app
+    fn frontmost_app(&self) -> Option<AppContext>;
+    fn activate_app(&self, pid: i32) -> Result<(), String>;
+    /// Select `text` in the focused element, e.g. to undo a replacement
+    fn select_text(&self, text: &str) -> Result<(), String>;
+    /// Replace the selection in the focused element without the clipboard.
+    /// Implementations verify the field changed; on Err the selection covers
+    /// whatever should be replaced and the caller pastes instead.
+    fn write_selection(&self, text: &str) -> Result<(), String>;
+
+    /// Send the platform's copy shortcut to the frontmost app
+    fn copy(&self) -> Result<(), String>;
+    /// Send the platform's paste shortcut to the frontmost app
+    fn paste(&self) -> Result<(), String>;
+
+    fn clipboard_snapshot(&self) -> ClipboardSnapshot;
+    fn restore_clipboard(&self, snapshot: &ClipboardSnapshot) -> Result<(), String>;
+    /// Counter that changes on every clipboard write, where the platform keeps one
+    fn clipboard_change_count(&self) -> Option<i64>;
+    fn read_clipboard_text(&self) -> Option<String>;
+    /// HTML flavor of the clipboard, converting from RTF where possible
+    fn read_clipboard_html(&self) -> Option<String>;
+    fn write_clipboard_text(&self, text: &str) -> Result<(), String>;
+    fn write_clipboard_html(&self, html: &str, plain: &str) -> Result<(), String>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_context_keeps_text_nearest_the_selection() {
+        let context = clip_context("One two three ", " four five six", 9);
+        assert_eq!(context.before, "three ");
+        assert_eq!(context.after, " four");
+    }
+
+    #[test]
+    fn clip_context_keeps_whole_words_at_the_limit() {
+        let context = clip_context("alpha beta ", " gamma delta", 5);
+        assert_eq!(context.before, "beta ");
+        assert_eq!(context.after, "");
+        let context = clip_context("ab cd", "ef gh", 10);
+        assert_eq!((context.before.as_str(), context.after.as_str()), ("ab cd", "ef gh"));
+    }
+
+    #[test]
+    fn clip_context_counts_characters_not_bytes() {
+        let context = clip_context("日本語のテキスト", "🦊🦊🦊", 2);
+        // One long word is clipped rather than dropped
+        assert_eq!(context.before, "スト");
+        assert_eq!(context.after, "🦊🦊");
+        assert_eq!(clip_context("abc", "def", 0), SelectionContext::default());
+    }
+}
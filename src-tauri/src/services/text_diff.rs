@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+
+/// Bail out to a whole-text replacement when the Myers trace would exceed this
+/// many cells; keeps pathological inputs from allocating without bound.
+const MAX_TRACE_CELLS: usize = 8_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffGranularity {
+    Word,
+    Char,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffSpan {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+/// Diff `original` against `result`, merging adjacent tokens of the same kind
+pub fn diff(original: &str, result: &str, granularity: DiffGranularity) -> Vec<DiffSpan> {
+    let (a, b) = match granularity {
+        DiffGranularity::Word => (tokenize_words(original), tokenize_words(result)),
+        DiffGranularity::Char => (tokenize_chars(original), tokenize_chars(result)),
+    };
+
+    let mut spans: Vec<DiffSpan> = Vec::new();
+    for (kind, token) in diff_tokens(&a, &b) {
+        match spans.last_mut() {
+            Some(last) if last.kind == kind => last.text.push_str(token),
+            _ => spans.push(DiffSpan {
+                kind,
+                text: token.to_string(),
+            }),
+        }
+    }
+    spans
+}
+
+/// Number of word-level change hunks; a deletion directly followed by an
+/// insertion (a replacement) counts once.
+pub fn changes_count(original: &str, result: &str) -> u32 {
+    let mut count = 0;
+    let mut in_change = false;
+    for span in diff(original, result, DiffGranularity::Word) {
+        let changed = span.kind != DiffKind::Equal;
+        if changed && !in_change {
+            count += 1;
+        }
+        in_change = changed;
+    }
+    count
+}
+
+/// Split into runs of word characters, runs of whitespace, and single
+/// punctuation characters so edits line up with what a reader sees as words.
+fn tokenize_words(text: &str) -> Vec<&str> {
+    #[derive(PartialEq)]
+    enum Class {
+        Word,
+        Space,
+        Other,
+    }
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            Class::Word
+        } else if c.is_whitespace() {
+            Class::Space
+        } else {
+            Class::Other
+        }
+    };
+
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut prev: Option<Class> = None;
+    for (i, c) in text.char_indices() {
+        let current = class(c);
+        let boundary = match &prev {
+            Some(p) => *p != current || current == Class::Other,
+            None => false,
+        };
+        if boundary {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        prev = Some(current);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+fn tokenize_chars(text: &str) -> Vec<&str> {
+    text.char_indices()
+        .map(|(i, c)| &text[i..i + c.len_utf8()])
+        .collect()
+}
+
+/// Token-level diff: common prefix/suffix are stripped, the middle is run
+/// through Myers' O(ND) algorithm.
+fn diff_tokens<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<(DiffKind, &'a str)> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+
+    let mut ops: Vec<(DiffKind, &'a str)> = a[..prefix].iter().map(|t| (DiffKind::Equal, *t)).collect();
+    match myers(a_mid, b_mid) {
+        Some(mid) => ops.extend(mid),
+        None => {
+            ops.extend(a_mid.iter().map(|t| (DiffKind::Delete, *t)));
+            ops.extend(b_mid.iter().map(|t| (DiffKind::Insert, *t)));
+        }
+    }
+    ops.extend(a[a.len() - suffix..].iter().map(|t| (DiffKind::Equal, *t)));
+    ops
+}
+
+fn myers<'a>(a: &[&'a str], b: &[&'a str]) -> Option<Vec<(DiffKind, &'a str)>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let idx = |k: isize| (k + offset) as usize;
+
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max as isize {
+        if (trace.len() + 1) * v.len() > MAX_TRACE_CELLS {
+            return None;
+        }
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let mut x = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+                v[idx(k + 1)]
+            } else {
+                v[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    // Walk the trace backwards from (n, m) to recover the edit script
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[idx(prev_k)];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            ops.push((DiffKind::Equal, a[x as usize - 1]));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops.push((DiffKind::Insert, b[y as usize - 1]));
+            } else {
+                ops.push((DiffKind::Delete, a[x as usize - 1]));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    ops.reverse();
+    Some(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(kind: DiffKind, text: &str) -> DiffSpan {
+        DiffSpan {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    fn rebuild(spans: &[DiffSpan], keep: DiffKind) -> String {
+        spans
+            .iter()
+            .filter(|s| s.kind == DiffKind::Equal || s.kind == keep)
+            .map(|s| s.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn tokenizes_words_spaces_and_punctuation() {
+        assert_eq!(
+            tokenize_words("Hi there, you!!"),
+            vec!["Hi", " ", "there", ",", " ", "you", "!", "!"]
+        );
+    }
+
+    #[test]
+    fn word_diff_marks_replaced_word() {
+        let spans = diff("teh quick fox", "the quick fox", DiffGranularity::Word);
+        assert_eq!(
+            spans,
+            vec![
+                span(DiffKind::Delete, "teh"),
+                span(DiffKind::Insert, "the"),
+                span(DiffKind::Equal, " quick fox"),
+            ]
+        );
+    }
+
+    #[test]
+    fn char_diff_is_finer() {
+        let spans = diff("colour", "color", DiffGranularity::Char);
+        assert_eq!(
+            spans,
+            vec![
+                span(DiffKind::Equal, "colo"),
+                span(DiffKind::Delete, "u"),
+                span(DiffKind::Equal, "r"),
+            ]
+        );
+    }
+
+    #[test]
+    fn spans_rebuild_both_texts() {
+        let original = "Their going to the store, and it's raining.\nSee you later";
+        let result = "They're going to the shop; it is raining.\n\nSee you soon";
+        for granularity in [DiffGranularity::Word, DiffGranularity::Char] {
+            let spans = diff(original, result, granularity);
+            assert_eq!(rebuild(&spans, DiffKind::Delete), original);
+            assert_eq!(rebuild(&spans, DiffKind::Insert), result);
+        }
+    }
+
+    #[test]
+    fn identical_and_empty_inputs() {
+        assert_eq!(diff("same", "same", DiffGranularity::Word), vec![span(DiffKind::Equal, "same")]);
+        assert_eq!(diff("", "new", DiffGranularity::Word), vec![span(DiffKind::Insert, "new")]);
+        assert!(diff("", "", DiffGranularity::Char).is_empty());
+    }
+
+    #[test]
+    fn counts_change_hunks() {
+        assert_eq!(changes_count("a b c", "a b c"), 0);
+        assert_eq!(changes_count("teh cat sat", "the cat sat"), 1);
+        assert_eq!(changes_count("teh cat sat on teh mat", "the cat sat on the mat"), 2);
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// How many replacements are kept for undo; older ones are dropped
+pub const UNDO_CAPACITY: usize = 20;
+
+/// A replacement that was pasted into another app
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndoEntry {
+    pub original_text: String,
+    pub result_text: String,
+    /// Process id of the app the result was pasted into
+    pub target_pid: Option<i32>,
+}
+
+/// Bounded stack of recent replacements, newest last
+pub struct UndoStack {
+    entries: Mutex<VecDeque<UndoEntry>>,
+    capacity: usize,
+}
+
+impl Default for UndoStack {
+    fn default() -> Self {
+        Self::with_capacity(UNDO_CAPACITY)
+    }
+}
+
+impl UndoStack {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    pub fn push(&self, entry: UndoEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        while entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    pub fn pop(&self) -> Option<UndoEntry> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(n: usize) -> UndoEntry {
+        UndoEntry {
+            original_text: format!("original {}", n),
+            result_text: format!("result {}", n),
+            target_pid: Some(42),
+        }
+    }
+
+    #[test]
+    fn pops_newest_first() {
+        let stack = UndoStack::default();
+        stack.push(entry(1));
+        stack.push(entry(2));
+        assert_eq!(stack.pop(), Some(entry(2)));
+        assert_eq!(stack.pop(), Some(entry(1)));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn drops_oldest_beyond_capacity() {
+        let stack = UndoStack::with_capacity(2);
+        for n in 1..=3 {
+            stack.push(entry(n));
+        }
+        assert_eq!(stack.pop(), Some(entry(3)));
+        assert_eq!(stack.pop(), Some(entry(2)));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let stack = UndoStack::with_capacity(0);
+        stack.push(entry(1));
+        assert_eq!(stack.pop(), None);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::services::objc::{class, rust_string, send, send_i32, with_pool};
+
+/// The active application as NSWorkspace reports it
+pub struct FrontmostApp {
+    pub name: String,
+    pub bundle_id: Option<String>,
+    pub pid: i32,
+}
+
+/// The application that currently receives key events. Our own windows are
+/// non-activating, so when a hotkey fires this is still the app the user
+/// was typing in.
+pub fn frontmost_app() -> Option<FrontmostApp> {
+    with_pool(|| unsafe {
+        let workspace = send(class("NSWorkspace"), "sharedWorkspace");
+        let app = send(workspace, "frontmostApplication");
+        if app.is_null() {
+            return None;
+        }
+        Some(FrontmostApp {
+            name: rust_string(send(app, "localizedName"))?,
+            bundle_id: rust_string(send(app, "bundleIdentifier")),
+            pid: send_i32(app, "processIdentifier"),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore = "asks NSWorkspace for whichever app is frontmost"]
+    fn frontmost_app_does_not_crash() {
+        // Headless runners may have no frontmost app; just verify the calls
+        if let Some(app) = frontmost_app() {
+            assert!(app.pid > 0);
+        }
+    }
+}
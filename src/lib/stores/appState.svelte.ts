@@ -1,5 +1,10 @@
 import { load } from "@tauri-apps/plugin-store";
-import type { RewriteAction, ProviderConfig } from "$lib/utils/commands";
+import type {
+  RewriteAction,
+  ProviderConfig,
+  RetentionSettings,
+  TemplateVariable,
+} from "$lib/utils/commands";
 
 const STORE_PATH = "settings.json";
 
@@ -46,6 +51,33 @@ let providers = $state<ProviderConfig[]>([
 
 let activeProviderId = $state("default");
 
+// Global shortcut for "Undo Last Omni Text Rewrite"; empty means tray menu only
+let undoHotkey = $state("");
+
+// Read by the backend before restoring the clipboard after a paste (text_interaction.rs)
+let clipboardRestoreDelayMs = $state(300);
+
+// Apps whose selection is never read (commands/rewrite.rs); defaults match
+// DEFAULT_SECURE_APPS in app_rules.rs
+let secureApps = $state<string[]>([
+  "com.1password.1password",
+  "com.agilebits.onepassword7",
+  "com.bitwarden.desktop",
+  "com.apple.keychainaccess",
+  "com.apple.Passwords",
+  "KeePassXC",
+]);
+
+// User {{variables}} for prompt templates, filled in by commands/rewrite.rs
+let templateVariables = $state<TemplateVariable[]>([]);
+
+// Read by the backend retention task, so the key must stay in sync with commands/history.rs
+let historyRetention = $state<RetentionSettings>({
+  max_age_days: null,
+  max_rows: null,
+  max_db_size_mb: null,
+});
+
 // Store instance (lazy loaded)
 let storeInstance: Awaited<ReturnType<typeof load>> | null = null;
 
@@ -70,6 +102,22 @@ async function loadState() {
     if (savedOnboarding !== null && savedOnboarding !== undefined)
       hasCompletedOnboarding = savedOnboarding;
 
+    const savedUndoHotkey = await store.get<string>("undoHotkey");
+    if (savedUndoHotkey !== null && savedUndoHotkey !== undefined) undoHotkey = savedUndoHotkey;
+
+    const savedRestoreDelay = await store.get<number>("clipboardRestoreDelayMs");
+    if (savedRestoreDelay !== null && savedRestoreDelay !== undefined)
+      clipboardRestoreDelayMs = savedRestoreDelay;
+
+    const savedSecureApps = await store.get<string[]>("secureApps");
+    if (savedSecureApps) secureApps = savedSecureApps;
+
+    const savedVariables = await store.get<TemplateVariable[]>("templateVariables");
+    if (savedVariables) templateVariables = savedVariables;
+
+    const savedRetention = await store.get<RetentionSettings>("historyRetention");
+    if (savedRetention) historyRetention = savedRetention;
+
     const savedActions = await store.get<RewriteAction[]>("actions");
     if (savedActions && savedActions.length > 0) actions = savedActions;
 
@@ -103,6 +151,11 @@ async function saveState() {
     await store.set("actions", actions);
     await store.set("providers", providers);
     await store.set("activeProviderId", activeProviderId);
+    await store.set("historyRetention", historyRetention);
+    await store.set("undoHotkey", undoHotkey);
+    await store.set("clipboardRestoreDelayMs", clipboardRestoreDelayMs);
+    await store.set("secureApps", secureApps);
+    await store.set("templateVariables", templateVariables);
   } catch (e) {
     console.error("Failed to save state:", e);
   }
@@ -136,6 +189,21 @@ export const appState = {
   get activeProviderId() { return activeProviderId; },
   set activeProviderId(v: string) { activeProviderId = v; saveState(); },
 
+  get historyRetention() { return historyRetention; },
+  set historyRetention(v: RetentionSettings) { historyRetention = v; saveState(); },
+
+  get undoHotkey() { return undoHotkey; },
+  set undoHotkey(v: string) { undoHotkey = v; saveState(); },
+
+  get clipboardRestoreDelayMs() { return clipboardRestoreDelayMs; },
+  set clipboardRestoreDelayMs(v: number) { clipboardRestoreDelayMs = v; saveState(); },
+
+  get secureApps() { return secureApps; },
+  set secureApps(v: string[]) { secureApps = v; saveState(); },
+
+  get templateVariables() { return templateVariables; },
+  set templateVariables(v: TemplateVariable[]) { templateVariables = v; saveState(); },
+
   /** Active provider (computed from providers + activeProviderId) */
   get provider(): ProviderConfig {
     return providers.find(p => p.id === activeProviderId) ?? providers[0];
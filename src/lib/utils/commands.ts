@@ -9,8 +9,101 @@ export interface RewriteAction {
   user_template: string;
   output_rules: string;
   enabled: boolean;
+  replace_mode?: ReplaceMode;
+  preserve_formatting?: boolean;
+  /** App names or bundle ids the action is limited to; empty means all */
+  include_apps?: string[];
+  exclude_apps?: string[];
+  app_overrides?: AppPromptOverride[];
+  /** Characters on each side for {{context_before}}/{{context_after}}; null uses 500 */
+  context_chars?: number | null;
+  /** What to work on when nothing is selected */
+  fallback_scope?: FallbackScope;
+  /** Asked for in the quick-input panel each time the action runs */
+  inputs?: ActionInput[];
+  /** Run instead of the action's own prompts, each rewriting the previous output */
+  steps?: ActionStep[];
+  /** Alternatives to generate and pick from in the preview; null or 1 for one */
+  candidates?: number | null;
+  /**
+   * [input, output] pairs sent before the real input; each input goes
+   * through user_template as {{text}}
+   */
+  examples?: [string, string][];
 }
 
+/** Another action's prompts when action_id is set, otherwise the step's own */
+export interface ActionStep {
+  action_id?: string | null;
+  /** Blank uses the referenced action's name */
+  name?: string;
+  system_prompt?: string;
+  user_template?: string;
+  output_rules?: string;
+  /** Provider id; null uses the active provider */
+  provider_id?: string | null;
+  /** Overrides the provider's model */
+  model?: string | null;
+}
+
+export interface StepOutput {
+  name: string;
+  provider: string;
+  model: string;
+  text: string;
+  duration_ms: number;
+  tokens_used: number | null;
+}
+
+export type ActionInputKind = "text" | "choice";
+
+/** A per-run parameter used in the template as {{name}} */
+export interface ActionInput {
+  name: string;
+  /** Blank uses the name */
+  label?: string;
+  kind?: ActionInputKind;
+  options?: string[];
+  /** For choices, blank picks the first option */
+  default?: string;
+}
+
+export interface InputRequest {
+  request_id: string;
+  action_name: string;
+  inputs: ActionInput[];
+}
+
+/** Prompt fields replaced in a specific app; null keeps the action's value */
+export interface AppPromptOverride {
+  app: string;
+  system_prompt?: string | null;
+  user_template?: string | null;
+  output_rules?: string | null;
+}
+
+export interface AppContext {
+  name: string;
+  bundle_id: string | null;
+  window_title: string | null;
+}
+
+export interface SelectedText {
+  text: string;
+  markdown: string | null;
+  app: AppContext | null;
+}
+
+/** A user-defined `{{name}}` for prompt templates */
+export interface TemplateVariable {
+  name: string;
+  value: string;
+}
+
+export type ReplaceMode = "immediate" | "preview";
+
+export type FallbackScope = "none" | "field" | "line" | "paragraph";
+
 export interface ProviderConfig {
   id: string;
   name: string;
@@ -30,6 +123,93 @@ export interface HistoryEntry {
   model: string;
   duration_ms: number;
   tokens_used: number | null;
+  changes_count: number | null;
+  app_bundle_id?: string | null;
+  window_title?: string | null;
+  /** Intermediate outputs of a multi-step action */
+  steps?: StepOutput[];
+  /** Every alternative offered when the action generates several */
+  candidates?: string[];
+}
+
+export interface HistoryFilter {
+  query?: string | null;
+  action_name?: string | null;
+  provider?: string | null;
+  model?: string | null;
+  app_name?: string | null;
+  from?: string | null;
+  to?: string | null;
+  min_duration_ms?: number | null;
+}
+
+export interface HistoryImportReport {
+  inserted: number;
+  skipped: number;
+  invalid: number;
+}
+
+export interface RetentionSettings {
+  max_age_days: number | null;
+  max_rows: number | null;
+  max_db_size_mb: number | null;
+}
+
+export interface PruneReport {
+  dry_run: boolean;
+  removed_by_age: number;
+  removed_by_count: number;
+  removed_by_size: number;
+  db_size_bytes: number;
+  vacuumed: boolean;
+}
+
+export interface DiffSpan {
+  kind: "equal" | "insert" | "delete";
+  text: string;
+}
+
+export type HistoryExportFormat = "jsonl" | "csv" | "markdown";
+
+export interface PendingReplacement {
+  request_id: string;
+  /** Plain text of the selection, recorded for undo */
+  original_text: string;
+  /** The selection as shown and diffed in the panel; Markdown when `markdown` is set */
+  display_original: string;
+  result_text: string;
+  diff: DiffSpan[];
+  target_pid: number | null;
+  markdown: boolean;
+  /** Alternatives to choose between, result_text first; empty for one result */
+  candidates: string[];
+}
+
+export interface ReplacementResolved {
+  request_id: string;
+  accepted: boolean;
+  text: string | null;
+  /** Why the replacement couldn't be pasted or the app refocused */
+  error: string | null;
+}
+
+export type UndoOutcome = "restored" | "copied";
+
+export interface UndoCompleted {
+  outcome: UndoOutcome | null;
+  error: string | null;
+}
+
+export interface RewriteResult {
+  original_text: string;
+  result_text: string;
+  duration_ms: number;
+  tokens_used: number | null;
+  app: AppContext | null;
+  /** Set when the action previews its result; nothing was pasted yet */
+  pending: PendingReplacement | null;
+  steps: StepOutput[];
+  candidates: string[];
 }
 
 export interface LLMResponse {
@@ -50,8 +230,61 @@ export async function getSelectedText(): Promise<string> {
   return invoke("get_selected_text");
 }
 
-export async function replaceSelectedText(text: string): Promise<void> {
-  return invoke("replace_selected_text", { text });
+export async function getSelectedRichText(): Promise<SelectedText> {
+  return invoke("get_selected_rich_text");
+}
+
+/**
+ * Pass `originalText` to make the replacement undoable; with `markdown` the
+ * text is pasted as rich text
+ */
+export async function replaceSelectedText(
+  text: string,
+  originalText?: string,
+  markdown = false
+): Promise<void> {
+  return invoke("replace_selected_text", { text, originalText: originalText ?? null, markdown });
+}
+
+export async function undoLastRewrite(): Promise<UndoOutcome> {
+  return invoke("undo_last_rewrite");
+}
+
+// Preview before replace
+export async function stageReplacement(
+  originalText: string,
+  resultText: string,
+  markdown = false
+): Promise<PendingReplacement> {
+  return invoke("stage_replacement", { originalText, resultText, markdown });
+}
+
+export async function getPendingReplacement(requestId: string): Promise<PendingReplacement> {
+  return invoke("get_pending_replacement", { requestId });
+}
+
+export async function acceptReplacement(requestId: string, text?: string): Promise<void> {
+  return invoke("accept_replacement", { requestId, text: text ?? null });
+}
+
+export async function rejectReplacement(requestId: string): Promise<void> {
+  return invoke("reject_replacement", { requestId });
+}
+
+// Quick-input panel
+/** Error `runAction` rejects with when the input panel is dismissed */
+export const INPUT_CANCELLED = "Cancelled";
+
+export async function getInputRequest(requestId: string): Promise<InputRequest> {
+  return invoke("get_input_request", { requestId });
+}
+
+export async function submitInputs(requestId: string, values: Record<string, string>): Promise<void> {
+  return invoke("submit_inputs", { requestId, values });
+}
+
+export async function cancelInputs(requestId: string): Promise<void> {
+  return invoke("cancel_inputs", { requestId });
 }
 
 export async function checkAccessibilityPermission(): Promise<boolean> {
@@ -63,19 +296,34 @@ export async function requestAccessibilityPermission(): Promise<void> {
 }
 
 // LLM Provider
+/** Read the selection, rewrite it with `action` and paste or preview the result */
+export async function runAction(action: RewriteAction, provider: ProviderConfig): Promise<RewriteResult> {
+  return invoke("run_action", { action, provider });
+}
+
+/** Check the action's templates parse and only use known variables */
+export async function validateAction(
+  action: RewriteAction,
+  variables: TemplateVariable[]
+): Promise<void> {
+  return invoke("validate_action", { action, variables });
+}
+
 export async function callLLM(
   baseUrl: string,
   apiKey: string,
   model: string,
   systemPrompt: string,
   userPrompt: string,
-  timeoutSecs: number
+  timeoutSecs: number,
+  examples: [string, string][] = []
 ): Promise<LLMResponse> {
   return invoke("call_llm", {
     baseUrl,
     apiKey,
     model,
     systemPrompt,
+    examples,
     userPrompt,
     timeoutSecs,
   });
@@ -108,6 +356,9 @@ export async function deleteApiKey(providerId: string): Promise<void> {
 }
 
 // Hotkeys
+/** Hotkey id that triggers undo instead of an action */
+export const UNDO_HOTKEY_ID = "undo-last-rewrite";
+
 export async function registerHotkey(
   id: string,
   shortcut: string
@@ -123,11 +374,65 @@ export async function unregisterAllHotkeys(): Promise<void> {
   return invoke("unregister_all_hotkeys");
 }
 
-// History (init handled by SQL plugin migration)
+// History (database opened and migrated by the backend at startup)
 export async function initHistoryDb(): Promise<void> {
   return invoke("init_history_db");
 }
 
+export async function addHistoryEntry(entry: HistoryEntry): Promise<void> {
+  return invoke("add_history_entry", { entry });
+}
+
+export async function searchHistory(
+  filter: HistoryFilter,
+  limit: number
+): Promise<HistoryEntry[]> {
+  return invoke("search_history", { filter, limit });
+}
+
+export async function deleteHistoryEntry(id: string): Promise<void> {
+  return invoke("delete_history_entry", { id });
+}
+
+export async function clearHistory(): Promise<void> {
+  return invoke("clear_history");
+}
+
+export async function exportHistory(
+  filter: HistoryFilter,
+  format: HistoryExportFormat,
+  path: string
+): Promise<number> {
+  return invoke("export_history", { filter, format, path });
+}
+
+export async function pruneHistory(
+  settings: RetentionSettings,
+  dryRun: boolean
+): Promise<PruneReport> {
+  return invoke("prune_history", { settings, dryRun });
+}
+
+/** Add an entry's original and result to the action's examples; save the returned action */
+export async function promoteHistoryExample(
+  action: RewriteAction,
+  entryId: string
+): Promise<RewriteAction> {
+  return invoke("promote_history_example", { action, entryId });
+}
+
+export async function diffTexts(
+  original: string,
+  result: string,
+  granularity: "word" | "char" = "word"
+): Promise<DiffSpan[]> {
+  return invoke("diff_texts", { original, result, granularity });
+}
+
+export async function importHistory(path: string): Promise<HistoryImportReport> {
+  return invoke("import_history", { path });
+}
+
 // Tray icon state
 export async function setTrayState(state: 'ready' | 'processing' | 'error'): Promise<void> {
   return invoke("set_tray_state", { state });
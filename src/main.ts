@@ -1,9 +1,20 @@
 import App from "./App.svelte";
+import PreviewPanel from "./lib/views/PreviewPanel.svelte";
+import QuickInputPanel from "./lib/views/QuickInputPanel.svelte";
 import { mount } from "svelte";
 import "./app.css";
 
-const app = mount(App, {
-  target: document.getElementById("app")!,
-});
+// Preview and input panels are separate windows loaded with
+// ?view=preview|input&request=<id>
+const params = new URLSearchParams(window.location.search);
+const target = document.getElementById("app")!;
+const props = { requestId: params.get("request") ?? "" };
+
+const app =
+  params.get("view") === "preview"
+    ? mount(PreviewPanel, { target, props })
+    : params.get("view") === "input"
+      ? mount(QuickInputPanel, { target, props })
+      : mount(App, { target });
 
 export default app;